@@ -0,0 +1,83 @@
+// Typo-tolerant word matching for `?fuzzy=true` search. Distances are plain
+// Levenshtein over chars; the allowance grows with the word so short words,
+// where one edit changes the meaning, still have to match exactly.
+
+// Edits allowed between a query word of `len` chars and a candidate
+pub fn max_distance(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Smallest distance between `term` and any whitespace-separated word of
+// `text`, if it is within the allowance for `term`. Both should already be
+// normalized the same way.
+pub fn closest_word(term: &str, text: &str) -> Option<usize> {
+    let allowed = max_distance(term.chars().count());
+    if allowed == 0 {
+        return None;
+    }
+
+    text.split_whitespace()
+        .map(|word| levenshtein(term, word))
+        .filter(|&distance| distance <= allowed)
+        .min()
+}
+
+// Insertions, deletions and substitutions needed to turn `a` into `b`,
+// keeping one row of the table at a time
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_single_edits() {
+        assert_eq!(levenshtein("chiken", "chicken"), 1);
+        assert_eq!(levenshtein("chicken", "chiken"), 1);
+        assert_eq!(levenshtein("chickem", "chicken"), 1);
+        assert_eq!(levenshtein("chicken", "chicken"), 0);
+        assert_eq!(levenshtein("", "egg"), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn allowance_grows_with_the_word() {
+        assert_eq!(max_distance(3), 0);
+        assert_eq!(max_distance(4), 1);
+        assert_eq!(max_distance(7), 1);
+        assert_eq!(max_distance(8), 2);
+    }
+
+    #[test]
+    fn finds_the_closest_word_within_the_allowance() {
+        assert_eq!(closest_word("chiken", "chicken soup"), Some(1));
+        assert_eq!(closest_word("soop", "chicken soup"), Some(1));
+        assert_eq!(closest_word("chkn", "chicken soup"), None);
+        assert_eq!(closest_word("tomatoe", "tomato and basil"), Some(1));
+    }
+
+    #[test]
+    fn short_words_must_match_exactly() {
+        assert_eq!(closest_word("egs", "eggs"), None);
+        assert_eq!(closest_word("pie", "pie"), None);
+    }
+}
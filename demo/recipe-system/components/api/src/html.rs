@@ -0,0 +1,131 @@
+// HTML fragment rendering of a recipe for server-side rendered pages. Every
+// piece of recipe text is user-supplied, so all of it goes through `escape`
+// before it reaches the markup; only the fixed tags here are trusted.
+
+use crate::RecipeJson;
+
+pub fn render_recipe(recipe: &RecipeJson) -> String {
+    let mut html = String::from("<article class=\"recipe\">\n");
+
+    html.push_str(&format!("  <h1>{}</h1>\n", escape(&recipe.name)));
+    if let Some(description) = recipe.description.as_deref().filter(|d| !d.trim().is_empty()) {
+        html.push_str(&format!("  <p class=\"description\">{}</p>\n", escape(description)));
+    }
+    html.push_str(&format!(
+        "  <p class=\"meta\">Serves {} &middot; Prep {} min &middot; Cook {} min</p>\n",
+        recipe.servings, recipe.prep_time_mins, recipe.cook_time_mins
+    ));
+
+    html.push_str("  <h2>Ingredients</h2>\n  <ul class=\"ingredients\">\n");
+    for ingredient in &recipe.ingredients {
+        let mut line = format!("{} {} {}", ingredient.amount, ingredient.unit, ingredient.name);
+        if ingredient.optional {
+            line.push_str(" (optional)");
+        }
+        if let Some(notes) = ingredient.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+            line.push_str(&format!(", {}", notes));
+        }
+        // Collapse the double space left by an empty unit, e.g. "2  eggs"
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        html.push_str(&format!("    <li>{}</li>\n", escape(&line)));
+    }
+    html.push_str("  </ul>\n");
+
+    let mut steps: Vec<_> = recipe.instructions.iter().collect();
+    steps.sort_by_key(|step| step.order);
+
+    html.push_str("  <h2>Steps</h2>\n  <ol class=\"steps\">\n");
+    for step in steps {
+        html.push_str(&format!("    <li>{}</li>\n", escape(&step.instruction)));
+    }
+    html.push_str("  </ol>\n</article>\n");
+
+    html
+}
+
+// Escapes text for use in element content and quoted attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const HOSTILE: &str = "<script>alert(\"Tom & Jerry's\")</script>";
+
+    fn recipe(description: &str, instructions: serde_json::Value) -> RecipeJson {
+        serde_json::from_value(json!({
+            "id": "soup",
+            "name": HOSTILE,
+            "description": description,
+            "ingredients": [
+                { "name": HOSTILE, "amount": 2.0, "unit": "", "optional": false, "notes": HOSTILE, "calories_per_unit": null }
+            ],
+            "instructions": instructions,
+            "servings": 2,
+            "prep_time_mins": 5,
+            "cook_time_mins": 10,
+            "difficulty": "easy",
+            "tags": [],
+            "dietary_info": [],
+            "author": null,
+            "created_at": 0,
+            "updated_at": 0
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn escapes_every_piece_of_recipe_text() {
+        let html = render_recipe(&recipe(HOSTILE, json!([{ "order": 1, "instruction": HOSTILE, "duration_mins": null }])));
+
+        let escaped = "&lt;script&gt;alert(&quot;Tom &amp; Jerry&#39;s&quot;)&lt;/script&gt;";
+        assert!(!html.contains("<script"));
+        assert!(!html.contains('\''));
+        assert!(!html.contains("Tom & Jerry"));
+        assert!(html.contains(&format!("<h1>{}</h1>", escaped)));
+        assert!(html.contains(&format!("<p class=\"description\">{}</p>", escaped)));
+        assert!(html.contains(&format!("<li>2 {}, {}</li>", escaped, escaped)));
+        assert!(html.contains(&format!("<li>{}</li>\n  </ol>", escaped)));
+    }
+
+    #[test]
+    fn omits_an_empty_description() {
+        let steps = json!([{ "order": 1, "instruction": "Stir", "duration_mins": null }]);
+        assert!(!render_recipe(&recipe("", steps.clone())).contains("class=\"description\""));
+        assert!(!render_recipe(&recipe("   ", steps)).contains("class=\"description\""));
+    }
+
+    #[test]
+    fn lists_steps_by_order() {
+        let steps = json!([
+            { "order": 3, "instruction": "Serve", "duration_mins": null },
+            { "order": 1, "instruction": "Chop", "duration_mins": null },
+            { "order": 2, "instruction": "Simmer", "duration_mins": 20 }
+        ]);
+        let html = render_recipe(&recipe("", steps));
+        let chop = html.find("<li>Chop</li>").unwrap();
+        let simmer = html.find("<li>Simmer</li>").unwrap();
+        let serve = html.find("<li>Serve</li>").unwrap();
+        assert!(chop < simmer && simmer < serve);
+    }
+
+    #[test]
+    fn escape_covers_markup_and_quote_characters() {
+        assert_eq!(escape("a < b > c & \"d\" 'e'"), "a &lt; b &gt; c &amp; &quot;d&quot; &#39;e&#39;");
+        assert_eq!(escape("plain text"), "plain text");
+    }
+}
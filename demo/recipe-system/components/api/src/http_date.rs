@@ -0,0 +1,166 @@
+// HTTP dates (RFC 9110 section 5.6.7) for Last-Modified and
+// If-Modified-Since. Dates are always formatted as IMF-fixdate, e.g.
+// "Sun, 06 Nov 1994 08:49:37 GMT". Parsing also accepts the obsolete RFC 850
+// and asctime forms, with any mix of spaces, commas and dashes between fields.
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTH_NAMES: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// Unix seconds as an IMF-fixdate
+pub fn format(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let seconds_of_day = timestamp % 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        // 1970-01-01 was a Thursday
+        DAY_NAMES[(days % 7) as usize],
+        day,
+        MONTH_NAMES[(month - 1) as usize],
+        year,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+// Unix seconds for an HTTP date, or None if it can't be read. The weekday is
+// ignored, and two-digit RFC 850 years of 70 or more mean 19xx.
+pub fn parse(value: &str) -> Option<u64> {
+    let mut month = None;
+    let mut time = None;
+    let mut numbers = Vec::new();
+
+    for token in value.split([' ', ',', '-']).filter(|token| !token.is_empty()) {
+        if token.contains(':') {
+            time = Some(parse_time(token)?);
+        } else if token.bytes().all(|b| b.is_ascii_digit()) {
+            numbers.push(token.parse::<u32>().ok()?);
+        } else if let Some(index) = MONTH_NAMES
+            .iter()
+            .position(|name| token.get(..3).is_some_and(|prefix| prefix.eq_ignore_ascii_case(name)))
+        {
+            month = Some(index as u32 + 1);
+        }
+    }
+
+    // Day comes before year in all three forms
+    let (day, year) = match numbers[..] {
+        [day, year] => (day, year),
+        _ => return None,
+    };
+    let year = match year {
+        0..=69 => year + 2000,
+        70..=99 => year + 1900,
+        _ => year,
+    };
+    let month = month?;
+    if year < 1970 || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let days = days_from_civil(year as i64, month, day);
+    Some(days as u64 * 86_400 + time?)
+}
+
+// Gregorian month lengths, so "31 Feb" is rejected rather than rolling over
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// "HH:MM:SS" as seconds since midnight
+fn parse_time(token: &str) -> Option<u64> {
+    let mut parts = token.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date, after Howard
+// Hinnant's days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// Inverse of days_from_civil: (year, month, day)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = (if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_imf_fixdate() {
+        assert_eq!(format(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(format(784_111_777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(format(951_782_400), "Tue, 29 Feb 2000 00:00:00 GMT");
+    }
+
+    #[test]
+    fn parses_all_three_forms() {
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784_111_777));
+        assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), Some(784_111_777));
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), Some(784_111_777));
+    }
+
+    #[test]
+    fn round_trips_formatted_dates() {
+        for timestamp in [0, 68_169_599, 784_111_777, 951_782_400, 1_700_000_000, 4_107_542_399] {
+            assert_eq!(parse(&format(timestamp)), Some(timestamp));
+        }
+    }
+
+    #[test]
+    fn rejects_days_past_the_end_of_the_month() {
+        assert_eq!(parse("Tue, 31 Feb 2024 00:00:00 GMT"), None);
+        assert_eq!(parse("Thu, 31 Apr 2025 00:00:00 GMT"), None);
+        assert_eq!(parse("Thu, 00 Jan 2025 00:00:00 GMT"), None);
+        assert_eq!(parse("Thu, 32 Jan 2025 00:00:00 GMT"), None);
+        assert!(parse("Wed, 30 Apr 2025 00:00:00 GMT").is_some());
+        assert!(parse("Fri, 31 Jan 2025 00:00:00 GMT").is_some());
+    }
+
+    #[test]
+    fn february_has_29_days_only_in_leap_years() {
+        assert!(parse("Thu, 29 Feb 2024 00:00:00 GMT").is_some());
+        assert!(parse("Tue, 29 Feb 2000 00:00:00 GMT").is_some());
+        assert_eq!(parse("Sat, 29 Feb 2025 00:00:00 GMT"), None);
+        assert_eq!(parse("Mon, 29 Feb 2100 00:00:00 GMT"), None);
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(1900, 2), 28);
+    }
+
+    #[test]
+    fn rejects_malformed_dates() {
+        assert_eq!(parse("not a date"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 GMT"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(parse("Sun, 06 Nov 1969 08:49:37 GMT"), None);
+        assert_eq!(parse("Sun, 06 1994 08:49:37 GMT"), None);
+    }
+}
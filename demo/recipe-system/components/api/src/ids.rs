@@ -0,0 +1,171 @@
+// How IDs are minted for recipes created without one, chosen by the
+// `id_strategy` config key, and which IDs a client may choose. The functions
+// here only build and check candidate IDs; allocating sequence numbers and
+// checking slugs against existing keys needs the store, so that stays with
+// the caller.
+
+// Longest slug kept from a recipe name, before any collision suffix
+const MAX_SLUG_LEN: usize = 64;
+
+// Collection routes under /api/recipes/ that no recipe ID may shadow
+const RESERVED_SLUGS: [&str; 17] = [
+    "batch-delete", "bulk", "by-ingredient", "count", "deleted", "diets", "difficulty", "export",
+    "grouped", "import", "makeable", "popular", "quick", "random", "schema", "search", "tags",
+];
+
+pub enum IdStrategy {
+    // `recipe_{nanos}_{sequence}`, roughly creation-ordered
+    Timestamp,
+    // Random version 4 UUID
+    Uuid,
+    // Derived from the recipe name, e.g. "tomato-soup", then "tomato-soup-2"
+    Slug,
+}
+
+impl IdStrategy {
+    // Unknown or missing values fall back to UUIDs
+    pub fn from_config(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            Some("timestamp") => IdStrategy::Timestamp,
+            Some("slug") => IdStrategy::Slug,
+            _ => IdStrategy::Uuid,
+        }
+    }
+}
+
+pub fn timestamp_id(nanos: u64, sequence: u64) -> String {
+    format!("recipe_{}_{}", nanos, sequence)
+}
+
+// Formats 128 random bits as a version 4, variant 1 UUID
+pub fn uuid_v4(high: u64, low: u64) -> String {
+    let high = (high & 0xffff_ffff_ffff_0fff) | 0x0000_0000_0000_4000;
+    let low = (low & 0x3fff_ffff_ffff_ffff) | 0x8000_0000_0000_0000;
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+// Lowercase ASCII letters and digits, with every other run of characters
+// collapsed to a single dash. Names with nothing usable slug to "recipe".
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.truncate(MAX_SLUG_LEN);
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "recipe".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+// Why a client-chosen ID can't be used, if it can't. Only letters, digits,
+// `-` and `_` are allowed, so every ID fits in one path segment and never
+// contains the `:` that separates key parts, and route names are refused
+// since GET would reach the route rather than the recipe.
+pub fn explicit_id_problem(id: &str) -> Option<String> {
+    if !id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+        return Some(format!("id '{}' may only contain letters, digits, '-' and '_'", id));
+    }
+    if RESERVED_SLUGS.contains(&id) {
+        return Some(format!("id '{}' is reserved for a route", id));
+    }
+    None
+}
+
+// The `attempt`th candidate for a slug: the slug itself, then "-2", "-3", ...
+// Route names skip straight to "-2".
+pub fn slug_candidate(slug: &str, attempt: u32) -> String {
+    if attempt <= 1 && !RESERVED_SLUGS.contains(&slug) {
+        slug.to_string()
+    } else {
+        format!("{}-{}", slug, attempt.max(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_ids_in_the_same_instant_differ() {
+        // The atomic sequence is what separates creates landing on the same nanosecond
+        let nanos = 1_700_000_000_123_456_789;
+        let ids: Vec<String> = (1..=1000).map(|sequence| timestamp_id(nanos, sequence)).collect();
+
+        let mut unique = ids.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), ids.len());
+        assert_eq!(ids[0], "recipe_1700000000123456789_1");
+    }
+
+    #[test]
+    fn strategy_defaults_to_uuid() {
+        assert!(matches!(IdStrategy::from_config(Some(" Slug ")), IdStrategy::Slug));
+        assert!(matches!(IdStrategy::from_config(Some("timestamp")), IdStrategy::Timestamp));
+        assert!(matches!(IdStrategy::from_config(Some("uuid")), IdStrategy::Uuid));
+        assert!(matches!(IdStrategy::from_config(Some("sequential")), IdStrategy::Uuid));
+        assert!(matches!(IdStrategy::from_config(None), IdStrategy::Uuid));
+    }
+
+    #[test]
+    fn uuids_carry_version_4_and_variant_1() {
+        assert_eq!(uuid_v4(0, 0), "00000000-0000-4000-8000-000000000000");
+        assert_eq!(uuid_v4(u64::MAX, u64::MAX), "ffffffff-ffff-4fff-bfff-ffffffffffff");
+        assert_eq!(uuid_v4(0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3210), "01234567-89ab-4def-bedc-ba9876543210");
+    }
+
+    #[test]
+    fn slugs_are_lowercase_and_dashed() {
+        assert_eq!(slugify("Tomato Soup"), "tomato-soup");
+        assert_eq!(slugify("  Mac & Cheese!! "), "mac-cheese");
+        assert_eq!(slugify("Crème brûlée"), "cr-me-br-l-e");
+        assert_eq!(slugify("???"), "recipe");
+    }
+
+    #[test]
+    fn long_slugs_are_cut_without_a_trailing_dash() {
+        let name = format!("{} pie", "a".repeat(MAX_SLUG_LEN - 1));
+        let slug = slugify(&name);
+        assert_eq!(slug, "a".repeat(MAX_SLUG_LEN - 1));
+    }
+
+    #[test]
+    fn slug_collisions_get_numeric_suffixes() {
+        assert_eq!(slug_candidate("tomato-soup", 1), "tomato-soup");
+        assert_eq!(slug_candidate("tomato-soup", 2), "tomato-soup-2");
+        assert_eq!(slug_candidate("tomato-soup", 3), "tomato-soup-3");
+    }
+
+    #[test]
+    fn route_names_never_become_slugs() {
+        for route in RESERVED_SLUGS {
+            assert_eq!(slug_candidate(route, 1), format!("{}-2", route));
+        }
+    }
+
+    #[test]
+    fn explicit_ids_must_fit_one_path_segment() {
+        for id in ["tomato-soup", "recipe_1700000000_1", "3f2c8e9a-5b1d-4c7e-9a2f-6d8b1e0c4a57", "Soup2"] {
+            assert_eq!(explicit_id_problem(id), None, "{}", id);
+        }
+        for id in ["a/b", "a?b", "soup:v1", "tomato soup", "süß"] {
+            assert!(explicit_id_problem(id).is_some(), "{}", id);
+        }
+        assert_eq!(explicit_id_problem("search").as_deref(), Some("id 'search' is reserved for a route"));
+    }
+}
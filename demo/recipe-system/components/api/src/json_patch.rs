@@ -0,0 +1,252 @@
+// JSON Patch (RFC 6902) covering the add, remove, replace and test
+// operations. Paths are JSON Pointers (RFC 6901). Operations are applied in
+// order and stop at the first failure, so callers should patch a copy to
+// leave the original untouched when a patch is rejected.
+
+use serde_json::Value;
+
+pub enum PatchError {
+    // Malformed operation, or a path that doesn't resolve
+    Invalid(String),
+    // A `test` operation didn't match the document
+    TestFailed(String),
+}
+
+pub fn apply(doc: &mut Value, operations: &[Value]) -> Result<(), PatchError> {
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(doc, operation).map_err(|e| match e {
+            PatchError::Invalid(message) => PatchError::Invalid(format!("operation {}: {}", index, message)),
+            PatchError::TestFailed(message) => PatchError::TestFailed(format!("operation {}: {}", index, message)),
+        })?;
+    }
+
+    Ok(())
+}
+
+fn apply_operation(doc: &mut Value, operation: &Value) -> Result<(), PatchError> {
+    let op = operation
+        .get("op")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("missing 'op'"))?;
+    let path = operation
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("missing 'path'"))?;
+    let tokens = parse_pointer(path)?;
+
+    match op {
+        "add" => add(doc, &tokens, operand(operation)?.clone()),
+        "remove" => remove(doc, &tokens),
+        "replace" => {
+            let value = operand(operation)?.clone();
+            let target = resolve_mut(doc, &tokens).ok_or_else(|| invalid(format!("path '{}' does not exist", path)))?;
+            *target = value;
+            Ok(())
+        }
+        "test" => {
+            let expected = operand(operation)?;
+            match resolve(doc, &tokens) {
+                Some(actual) if actual == expected => Ok(()),
+                _ => Err(PatchError::TestFailed(format!("value at '{}' does not match", path))),
+            }
+        }
+        other => Err(invalid(format!("unsupported op '{}'", other))),
+    }
+}
+
+// `value` may legitimately be null, so only a missing key is an error
+fn operand(operation: &Value) -> Result<&Value, PatchError> {
+    operation.get("value").ok_or_else(|| invalid("missing 'value'"))
+}
+
+fn add(doc: &mut Value, tokens: &[String], value: Value) -> Result<(), PatchError> {
+    let Some((last, parent_tokens)) = tokens.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+
+    match resolve_mut(doc, parent_tokens) {
+        Some(Value::Object(fields)) => {
+            fields.insert(last.clone(), value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => {
+            // "-" appends; otherwise the index may be one past the end
+            let index = if last == "-" { Some(items.len()) } else { parse_index(last) };
+            match index {
+                Some(index) if index <= items.len() => {
+                    items.insert(index, value);
+                    Ok(())
+                }
+                _ => Err(invalid(format!("array index '{}' is out of range", last))),
+            }
+        }
+        _ => Err(invalid("parent path does not exist")),
+    }
+}
+
+fn remove(doc: &mut Value, tokens: &[String]) -> Result<(), PatchError> {
+    let (last, parent_tokens) = tokens.split_last().ok_or_else(|| invalid("cannot remove the whole document"))?;
+
+    let removed = match resolve_mut(doc, parent_tokens) {
+        Some(Value::Object(fields)) => fields.remove(last.as_str()).is_some(),
+        Some(Value::Array(items)) => match parse_index(last) {
+            Some(index) if index < items.len() => {
+                items.remove(index);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+
+    if removed {
+        Ok(())
+    } else {
+        Err(invalid(format!("path '/{}' does not exist", tokens.join("/"))))
+    }
+}
+
+fn resolve<'a>(doc: &'a Value, tokens: &[String]) -> Option<&'a Value> {
+    tokens.iter().try_fold(doc, |current, token| match current {
+        Value::Object(fields) => fields.get(token.as_str()),
+        Value::Array(items) => items.get(parse_index(token)?),
+        _ => None,
+    })
+}
+
+fn resolve_mut<'a>(doc: &'a mut Value, tokens: &[String]) -> Option<&'a mut Value> {
+    tokens.iter().try_fold(doc, |current, token| match current {
+        Value::Object(fields) => fields.get_mut(token.as_str()),
+        Value::Array(items) => items.get_mut(parse_index(token)?),
+        _ => None,
+    })
+}
+
+// "" is the whole document; anything else must start with '/'
+fn parse_pointer(path: &str) -> Result<Vec<String>, PatchError> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rest = path
+        .strip_prefix('/')
+        .ok_or_else(|| invalid(format!("path '{}' must start with '/'", path)))?;
+
+    // ~1 must be unescaped before ~0 so "~01" becomes "~1", not "/"
+    Ok(rest.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+// Array indices are plain decimal without leading zeros
+fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn invalid(message: impl Into<String>) -> PatchError {
+    PatchError::Invalid(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patched(mut doc: Value, operations: Value) -> Result<Value, PatchError> {
+        let Value::Array(operations) = operations else { panic!("operations must be an array") };
+        apply(&mut doc, &operations).map(|_| doc)
+    }
+
+    fn error_message(result: Result<Value, PatchError>) -> String {
+        match result {
+            Err(PatchError::Invalid(message)) => format!("invalid: {}", message),
+            Err(PatchError::TestFailed(message)) => format!("test failed: {}", message),
+            Ok(doc) => panic!("expected an error, got {}", doc),
+        }
+    }
+
+    #[test]
+    fn add_inserts_fields_and_array_items() {
+        let doc = json!({ "name": "Soup", "tags": ["hot"] });
+        let result = patched(
+            doc,
+            json!([
+                { "op": "add", "path": "/servings", "value": 4 },
+                { "op": "add", "path": "/tags/0", "value": "quick" },
+                { "op": "add", "path": "/tags/-", "value": "winter" },
+            ]),
+        );
+        assert_eq!(result.ok(), Some(json!({ "name": "Soup", "servings": 4, "tags": ["quick", "hot", "winter"] })));
+    }
+
+    #[test]
+    fn remove_and_replace_need_an_existing_path() {
+        let doc = json!({ "name": "Soup", "tags": ["hot", "quick"] });
+        let result = patched(
+            doc.clone(),
+            json!([
+                { "op": "remove", "path": "/tags/0" },
+                { "op": "replace", "path": "/name", "value": "Stew" },
+            ]),
+        );
+        assert_eq!(result.ok(), Some(json!({ "name": "Stew", "tags": ["quick"] })));
+
+        let missing = patched(doc.clone(), json!([{ "op": "remove", "path": "/author" }]));
+        assert_eq!(error_message(missing), "invalid: operation 0: path '/author' does not exist");
+        let missing = patched(doc, json!([{ "op": "replace", "path": "/tags/5", "value": "x" }]));
+        assert_eq!(error_message(missing), "invalid: operation 0: path '/tags/5' does not exist");
+    }
+
+    #[test]
+    fn test_compares_values() {
+        let doc = json!({ "servings": 4, "description": null });
+        assert!(patched(doc.clone(), json!([{ "op": "test", "path": "/description", "value": null }])).is_ok());
+
+        let mismatch = patched(doc, json!([{ "op": "test", "path": "/servings", "value": "4" }]));
+        assert_eq!(error_message(mismatch), "test failed: operation 0: value at '/servings' does not match");
+    }
+
+    #[test]
+    fn stops_at_the_first_failing_operation() {
+        let mut doc = json!({ "name": "Soup" });
+        let operations = [
+            json!({ "op": "replace", "path": "/name", "value": "Stew" }),
+            json!({ "op": "move", "from": "/name", "path": "/title" }),
+            json!({ "op": "add", "path": "/servings", "value": 2 }),
+        ];
+        assert!(matches!(apply(&mut doc, &operations), Err(PatchError::Invalid(message)) if message == "operation 1: unsupported op 'move'"));
+        // Earlier operations have already been applied, hence patching a copy
+        assert_eq!(doc, json!({ "name": "Stew" }));
+    }
+
+    #[test]
+    fn pointers_unescape_tilde_sequences() {
+        let doc = json!({ "a/b": 1, "m~n": 2, "~1": 3 });
+        let result = patched(
+            doc,
+            json!([
+                { "op": "replace", "path": "/a~1b", "value": 10 },
+                { "op": "replace", "path": "/m~0n", "value": 20 },
+                { "op": "replace", "path": "/~01", "value": 30 },
+            ]),
+        );
+        assert_eq!(result.ok(), Some(json!({ "a/b": 10, "m~n": 20, "~1": 30 })));
+    }
+
+    #[test]
+    fn rejects_malformed_operations() {
+        let doc = json!({ "tags": [] });
+        assert_eq!(error_message(patched(doc.clone(), json!([{ "path": "/tags" }]))), "invalid: operation 0: missing 'op'");
+        assert_eq!(error_message(patched(doc.clone(), json!([{ "op": "add", "path": "/x" }]))), "invalid: operation 0: missing 'value'");
+        assert_eq!(
+            error_message(patched(doc.clone(), json!([{ "op": "add", "path": "tags", "value": 1 }]))),
+            "invalid: operation 0: path 'tags' must start with '/'"
+        );
+        assert_eq!(
+            error_message(patched(doc, json!([{ "op": "add", "path": "/tags/01", "value": 1 }]))),
+            "invalid: operation 0: array index '01' is out of range"
+        );
+    }
+}
@@ -7,373 +7,6110 @@ wit_bindgen::generate!({
 });
 
 use exports::wasi::http::incoming_handler::Guest;
+use wasi::blobstore::blobstore::{container_exists, create_container, get_container};
+use wasi::blobstore::container::Container;
+use wasi::blobstore::types::{IncomingValue, OutgoingValue};
 use wasi::http::types::*;
+use wasi::keyvalue::atomics::increment;
 use wasi::keyvalue::store::*;
-use wasi::logging::logging::*;
+use wasi::logging::logging::Level;
 
 use serde::{Deserialize, Serialize};
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeMap, HashSet};
+
+mod fuzzy;
+mod html;
+mod http_date;
+mod ids;
+mod json_patch;
+mod plural;
+mod query;
+mod schema;
+mod units;
+mod yaml;
+
+use query::Query;
 
 struct Component;
 
+// Request-scoped settings the response helpers need. A component instance
+// serves one request at a time, so `handle` resets this on every request.
+#[derive(Default)]
+struct RequestContext {
+    request_id: String,
+    accepts_gzip: bool,
+    // `?allow_gaps=true`, which every write path's step check honors
+    allow_step_gaps: bool,
+    // Set by write_response, for the timing log
+    status: Option<u16>,
+}
+
+impl RequestContext {
+    fn from_headers(headers: &Fields) -> Self {
+        // Reuse the caller's correlation ID when it is safe to echo into logs
+        let request_id = header_value(headers, "x-request-id")
+            .filter(|id| is_safe_identifier(id))
+            .unwrap_or_else(generate_request_id);
+
+        RequestContext {
+            request_id,
+            accepts_gzip: header_value(headers, "accept-encoding").is_some_and(|h| accepts_gzip(&h)),
+            allow_step_gaps: false,
+            status: None,
+        }
+    }
+}
+
+// Logs how long a request took once `handle` returns. Because it fires on
+// drop, every response path is timed without the send_* helpers knowing.
+struct RequestTimer {
+    method: String,
+    path: String,
+    started_at: u64,
+}
+
+impl RequestTimer {
+    fn start(method: &Method, path: &str) -> Self {
+        RequestTimer {
+            method: method_name(method),
+            path: path.to_string(),
+            started_at: current_timestamp_nanos(),
+        }
+    }
+}
+
+impl Drop for RequestTimer {
+    fn drop(&mut self) {
+        let elapsed_ms = current_timestamp_nanos().saturating_sub(self.started_at) as f64 / 1_000_000.0;
+        let status_code = REQUEST_CONTEXT.with(|ctx| ctx.borrow().status);
+        let status = status_code.map_or("-".to_string(), |code| code.to_string());
+        log(
+            Level::Info,
+            "recipe-api",
+            &format!("{} {} -> {} in {:.2}ms", self.method, self.path, status, elapsed_ms),
+        );
+
+        // Scrapes aren't counted, so polling /metrics doesn't inflate it
+        if self.path.trim_matches('/') != "metrics" {
+            if let Err(e) = open_bucket().and_then(|bucket| record_request_metrics(&bucket, &self.method, status_code)) {
+                log(Level::Warn, "recipe-api", &format!("Failed to record metrics: {}", e));
+            }
+        }
+    }
+}
+
+thread_local! {
+    static REQUEST_CONTEXT: RefCell<RequestContext> = RefCell::new(RequestContext::default());
+    // Resolved on first use; config doesn't change while an instance runs
+    static BUCKET_NAME: OnceCell<String> = const { OnceCell::new() };
+    // Whether the bucket can list keys, probed on the first write that asks
+    static KEY_LISTING: OnceCell<bool> = const { OnceCell::new() };
+    // (index, id) additions held by with_index_batch; None outside a batch
+    static PENDING_INDEX_ADDS: RefCell<Option<Vec<(String, String)>>> = const { RefCell::new(None) };
+}
+
+// Keyvalue bucket used when the `bucket_name` config key isn't set
+const DEFAULT_BUCKET_NAME: &str = "recipes";
+
+// Pagination defaults for the list endpoint
+const DEFAULT_PER_PAGE: usize = 20;
+const MAX_PER_PAGE: usize = 100;
+
+// Recipes returned by /related unless `?limit=` says otherwise
+const DEFAULT_RELATED_LIMIT: usize = 10;
+
+// Time limit for /quick unless `?max=` says otherwise, in minutes
+const DEFAULT_QUICK_MAX_MINS: u32 = 30;
+
+// Most required ingredients a recipe can lack and still be listed as `almost`
+const MAX_ALMOST_MISSING: usize = 2;
+
+// Recipes returned by /popular unless `?limit=` says otherwise
+const DEFAULT_POPULAR_LIMIT: usize = 10;
+
+// Sub-resources GET /api/recipes/{id} can embed via `?expand=`
+const EXPANSIONS: [&str; 2] = ["related", "nutrition"];
+
+// JSON bodies smaller than this aren't worth compressing
+const GZIP_MIN_BYTES: usize = 1024;
+
+// Accepted values for RecipeJson::difficulty
+const DIFFICULTY_LEVELS: [&str; 3] = ["easy", "medium", "hard"];
+
+// Upper bound for prep and cook time unless the `max_time_mins` config key
+// raises or lowers it: 24 hours
+const DEFAULT_MAX_TIME_MINS: u64 = 1440;
+
+// Key prefixes for the ID indexes kept in the bucket
+const RECIPE_INDEX: &str = "_recipe_ids";
+const FAVORITES_INDEX: &str = "_favorites";
+const DELETED_INDEX: &str = "_deleted_ids";
+const COLLECTION_INDEX: &str = "_collection_ids";
+
+// Recipe photos live in this blobstore container, one object per recipe ID
+const IMAGE_CONTAINER: &str = "recipe-images";
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+const IMAGE_CONTENT_TYPES: [&str; 3] = ["image/jpeg", "image/png", "image/webp"];
+
+// Streamed NDJSON imports: longest accepted line, how many records between
+// index flushes, and how many failures the summary lists
+const MAX_NDJSON_LINE_BYTES: usize = 1024 * 1024;
+const NDJSON_INDEX_FLUSH_EVERY: usize = 500;
+const MAX_REPORTED_FAILURES: usize = 100;
+
+// Past versions kept per recipe; older snapshots are deleted as new ones land
+const MAX_RETAINED_VERSIONS: u64 = 20;
+
+// How long an Idempotency-Key replays its original create
+const IDEMPOTENCY_TTL_SECS: u64 = 24 * 60 * 60;
+
+// Atomic counter mixed into generated recipe IDs
+const ID_SEQUENCE_KEY: &str = "_recipe_id_seq";
+
+// CORS policy advertised on every response
+const CORS_ALLOWED_METHODS: &str = "GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS";
+const CORS_ALLOWED_HEADERS: &str = "Content-Type, Idempotency-Key, If-Match, If-Modified-Since, If-None-Match, X-Admin-Token, X-Author, X-Client-Id, X-HTTP-Method-Override, X-Request-Id";
+const CORS_EXPOSED_HEADERS: &str = "Content-Disposition, ETag, Last-Modified, Location, Retry-After, X-Request-Id";
+
+// Longest client-supplied identifier (X-Request-Id, X-Client-Id) we'll use
+const MAX_IDENTIFIER_LEN: usize = 128;
+
+// Key prefixes for the request metrics counters
+const METRICS_REQUESTS_PREFIX: &str = "metrics:requests:";
+const METRICS_ERRORS_PREFIX: &str = "metrics:errors:";
+// Error statuses seen so far, so /metrics can find their counters
+const METRICS_STATUS_INDEX: &str = "_metrics_statuses";
+
+// Per-client rate limit defaults, each overridable through wasi config
+const DEFAULT_RATE_LIMIT_HEADER: &str = "x-client-id";
+const DEFAULT_RATE_LIMIT_REQUESTS: u64 = 100;
+const DEFAULT_RATE_LIMIT_WINDOW_SECS: u64 = 60;
+
 // Helper struct for JSON serialization
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct RecipeJson {
     id: String,
     name: String,
     description: Option<String>,
     ingredients: Vec<IngredientJson>,
     instructions: Vec<StepJson>,
-    servings: u8,
+    servings: u16,
     prep_time_mins: u32,
     cook_time_mins: u32,
     difficulty: String,
     tags: Vec<String>,
     dietary_info: Vec<String>,
+    // Who created the recipe; older recipes have none
+    author: Option<String>,
     created_at: u64,
     updated_at: u64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct IngredientJson {
     name: String,
     amount: f32,
     unit: String,
     optional: bool,
     notes: Option<String>,
+    calories_per_unit: Option<f32>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct StepJson {
     order: u8,
     instruction: String,
     duration_mins: Option<u32>,
 }
 
-impl Guest for Component {
-    fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
-        let path_with_query = request.path_with_query().unwrap_or("/".to_string());
+// Response form of a recipe, adding fields derived from the stored data.
+// RecipeJson stays the stored/input shape; parse_recipe drops these on the
+// way in.
+// The keys RecipeView adds to a recipe, which clients may echo back
+const RESPONSE_ONLY_FIELDS: [&str; 6] = ["total_time_mins", "problems", "_links", "all_notes", "average_rating", "rating_count"];
 
-        log(Level::Info, "recipe-api", &format!("Request: {}", path_with_query));
+#[derive(Serialize)]
+struct RecipeView {
+    #[serde(flatten)]
+    recipe: RecipeJson,
+    total_time_mins: u32,
+    // Lint results, only present when listing with `?incomplete=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    problems: Option<Vec<String>>,
+    // Hypermedia links, only present with `?links=true`
+    #[serde(rename = "_links", skip_serializing_if = "Option::is_none")]
+    links: Option<RecipeLinks>,
+    // Ingredient notes gathered in one place, only with `?notes=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    all_notes: Option<Vec<IngredientNote>>,
+    // `average_rating` and `rating_count`, only on GET /api/recipes/{id}
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    rating: Option<RatingSummary>,
+}
 
-        // Parse path
-        let parts: Vec<&str> = path_with_query.split('?').collect();
-        let path = parts[0];
-        let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+impl From<RecipeJson> for RecipeView {
+    fn from(recipe: RecipeJson) -> Self {
+        let total_time_mins = recipe.prep_time_mins.saturating_add(recipe.cook_time_mins);
+        RecipeView { recipe, total_time_mins, problems: None, links: None, all_notes: None, rating: None }
+    }
+}
 
-        // Route request
-        match request.method() {
-            Method::Get => handle_get(&path_segments, response_out),
-            Method::Post => handle_post(&path_segments, request, response_out),
-            Method::Put => handle_put(&path_segments, request, response_out),
-            Method::Delete => handle_delete(&path_segments, response_out),
-            _ => send_response(405, b"Method Not Allowed", response_out),
-        }
+impl RecipeView {
+    fn with_links(self) -> Self {
+        let links = RecipeLinks::for_recipe(&self.recipe.id);
+        RecipeView { links: Some(links), ..self }
+    }
+
+    // Ingredients without notes, or with blank ones, are skipped
+    fn with_notes(self) -> Self {
+        let notes = self
+            .recipe
+            .ingredients
+            .iter()
+            .filter_map(|ingredient| {
+                let note = ingredient.notes.as_deref()?.trim();
+                (!note.is_empty()).then(|| IngredientNote { ingredient: ingredient.name.clone(), note: note.to_string() })
+            })
+            .collect();
+        RecipeView { all_notes: Some(notes), ..self }
+    }
+
+    fn with_rating(self, rating: RatingSummary) -> Self {
+        RecipeView { rating: Some(rating), ..self }
     }
 }
 
-fn handle_get(path: &[&str], response_out: ResponseOutparam) {
-    match path {
-        ["api", "recipes"] => {
-            // List all recipes
-            match list_recipes() {
-                Ok(recipes) => {
-                    let json = serde_json::to_string(&recipes).unwrap();
-                    send_json_response(200, json.as_bytes(), response_out);
-                }
-                Err(e) => {
-                    log(Level::Error, "recipe-api", &format!("Error listing recipes: {:?}", e));
-                    send_response(500, b"Internal Server Error", response_out);
-                }
-            }
-        }
-        ["api", "recipes", id] => {
-            // Get specific recipe
-            match get_recipe(id) {
-                Ok(Some(recipe)) => {
-                    let json = serde_json::to_string(&recipe).unwrap();
-                    send_json_response(200, json.as_bytes(), response_out);
-                }
-                Ok(None) => {
-                    send_response(404, b"Recipe not found", response_out);
-                }
-                Err(e) => {
-                    log(Level::Error, "recipe-api", &format!("Error getting recipe: {:?}", e));
-                    send_response(500, b"Internal Server Error", response_out);
-                }
-            }
-        }
-        ["health"] => {
-            send_json_response(200, b"{\"status\":\"healthy\"}", response_out);
-        }
-        _ => {
-            send_response(404, b"Not Found", response_out);
-        }
+// One entry in `all_notes`. Steps have no notes of their own, so every note
+// comes from an ingredient.
+#[derive(Serialize)]
+struct IngredientNote {
+    ingredient: String,
+    note: String,
+}
+
+// Running totals of a recipe's 1-5 ratings. `average_rating` is null until
+// the first rating.
+#[derive(Serialize)]
+struct RatingSummary {
+    average_rating: Option<f64>,
+    rating_count: u64,
+    // Exact sum for the ETag, since the rounded average can hide a change
+    #[serde(skip)]
+    sum: u64,
+    // When the latest rating was recorded, for Last-Modified
+    #[serde(skip)]
+    rated_at: u64,
+}
+
+impl RatingSummary {
+    fn from_totals(sum: u64, count: u64, rated_at: u64) -> Self {
+        // One decimal place is all a star display needs
+        let average_rating = (count > 0).then(|| (sum as f64 / count as f64 * 10.0).round() / 10.0);
+        RatingSummary { average_rating, rating_count: count, sum, rated_at }
     }
 }
 
-fn handle_post(path: &[&str], request: IncomingRequest, response_out: ResponseOutparam) {
-    match path {
-        ["api", "recipes"] => {
-            // Create new recipe
-            match read_request_body(request) {
-                Ok(body) => {
-                    match serde_json::from_slice::<RecipeJson>(&body) {
-                        Ok(recipe_json) => {
-                            match create_recipe(recipe_json) {
-                                Ok(id) => {
-                                    let response = format!("{{\"id\":\"{}\"}}", id);
-                                    send_json_response(201, response.as_bytes(), response_out);
-                                }
-                                Err(e) => {
-                                    log(Level::Error, "recipe-api", &format!("Error creating recipe: {:?}", e));
-                                    send_response(500, b"Internal Server Error", response_out);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            log(Level::Error, "recipe-api", &format!("Invalid JSON: {:?}", e));
-                            send_response(400, b"Invalid JSON", response_out);
-                        }
-                    }
-                }
-                Err(_) => {
-                    send_response(400, b"Failed to read body", response_out);
-                }
-            }
-        }
-        _ => {
-            send_response(404, b"Not Found", response_out);
+#[derive(Deserialize)]
+struct RatingRequest {
+    // Wider than the valid range so 0 or 6 get a clear message, not a parse error
+    rating: i64,
+}
+
+// Where a client can go from a recipe. Paths must match the routes in handle_*.
+#[derive(Serialize)]
+struct RecipeLinks {
+    #[serde(rename = "self")]
+    self_link: Link,
+    update: Link,
+    delete: Link,
+    image: Link,
+}
+
+#[derive(Serialize)]
+struct Link {
+    href: String,
+    // Omitted for plain GETs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<&'static str>,
+}
+
+impl RecipeLinks {
+    fn for_recipe(id: &str) -> Self {
+        let href = format!("/api/recipes/{}", id);
+        RecipeLinks {
+            self_link: Link { href: href.clone(), method: None },
+            update: Link { href: href.clone(), method: Some("PUT") },
+            delete: Link { href: href.clone(), method: Some("DELETE") },
+            image: Link { href: format!("{}/image", href), method: None },
         }
     }
 }
 
-fn handle_put(path: &[&str], request: IncomingRequest, response_out: ResponseOutparam) {
-    match path {
-        ["api", "recipes", id] => {
-            // Update recipe
-            match read_request_body(request) {
-                Ok(body) => {
-                    match serde_json::from_slice::<RecipeJson>(&body) {
-                        Ok(mut recipe_json) => {
-                            recipe_json.id = id.to_string();
-                            match update_recipe(id, recipe_json) {
-                                Ok(_) => {
-                                    send_json_response(200, b"{\"status\":\"updated\"}", response_out);
-                                }
-                                Err(e) => {
-                                    log(Level::Error, "recipe-api", &format!("Error updating recipe: {:?}", e));
-                                    send_response(500, b"Internal Server Error", response_out);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            log(Level::Error, "recipe-api", &format!("Invalid JSON: {:?}", e));
-                            send_response(400, b"Invalid JSON", response_out);
-                        }
-                    }
-                }
-                Err(_) => {
-                    send_response(400, b"Failed to read body", response_out);
-                }
-            }
-        }
-        _ => {
-            send_response(404, b"Not Found", response_out);
-        }
+// Envelope for a single page of recipes
+#[derive(Serialize)]
+struct RecipePage {
+    data: Vec<RecipeView>,
+    total: usize,
+    page: usize,
+    per_page: usize,
+}
+
+// `{"data": [...], "meta": {...}}` for `?envelope=true`: the recipes from a
+// serialized RecipePage, with its paging fields moved under `meta`
+fn page_envelope(page: serde_json::Value) -> serde_json::Value {
+    let serde_json::Value::Object(mut meta) = page else {
+        return page;
+    };
+    let data = meta.remove("data").unwrap_or_default();
+    serde_json::json!({ "data": data, "meta": meta })
+}
+
+// `?fields=id,name` as field names, or None to return whole recipes.
+// Repeated `fields=` values are combined.
+fn requested_fields(query: &Query) -> Option<Vec<String>> {
+    let fields: Vec<String> = query
+        .get_all("fields")
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    (!fields.is_empty()).then_some(fields)
+}
+
+// Keep only the named top-level keys of a serialized recipe. Names that
+// aren't fields are ignored, so they simply don't appear in the output.
+// Underscored keys such as `_links` were asked for separately and are kept.
+fn project_fields(recipe: &mut serde_json::Value, fields: &[String]) {
+    if let serde_json::Value::Object(map) = recipe {
+        map.retain(|key, _| key.starts_with('_') || fields.contains(key));
     }
 }
 
-fn handle_delete(path: &[&str], response_out: ResponseOutparam) {
-    match path {
-        ["api", "recipes", id] => {
-            match delete_recipe(id) {
-                Ok(_) => {
-                    send_json_response(200, b"{\"status\":\"deleted\"}", response_out);
-                }
-                Err(e) => {
-                    log(Level::Error, "recipe-api", &format!("Error deleting recipe: {:?}", e));
-                    send_response(500, b"Internal Server Error", response_out);
-                }
+// `?expand=related,nutrition` as the known expansions, deduplicated.
+// Unknown names are ignored.
+fn requested_expansions(query: &Query) -> Vec<&'static str> {
+    let mut expansions = Vec::new();
+    for name in query.get_all("expand").iter().flat_map(|value| value.split(',')).map(str::trim) {
+        if let Some(known) = EXPANSIONS.iter().find(|known| **known == name) {
+            if !expansions.contains(known) {
+                expansions.push(*known);
             }
         }
-        _ => {
-            send_response(404, b"Not Found", response_out);
+    }
+    expansions
+}
+
+// The `_embedded` object for a recipe, computing only what was asked for
+fn embedded_resources(
+    store: &dyn RecipeStore,
+    recipe: &RecipeJson,
+    expansions: &[&'static str],
+) -> Result<serde_json::Map<String, serde_json::Value>, RecipeError> {
+    let mut embedded = serde_json::Map::new();
+
+    for expansion in expansions {
+        let value = match *expansion {
+            "related" => serde_json::to_value(related_recipes(store, recipe, DEFAULT_RELATED_LIMIT)?),
+            "nutrition" => serde_json::to_value(estimate_nutrition(recipe)),
+            _ => continue,
         }
+        .map_err(|e| RecipeError::Serialization(format!("Failed to serialize {}: {:?}", expansion, e)))?;
+        embedded.insert(expansion.to_string(), value);
     }
+
+    Ok(embedded)
 }
 
-fn list_recipes() -> Result<Vec<RecipeJson>, String> {
-    let bucket = open("recipes").map_err(|e| format!("Failed to open bucket: {:?}", e))?;
+// A search hit, with its relevance score and the best field the query matched
+#[derive(Serialize)]
+struct SearchResult {
+    score: u32,
+    matched_field: &'static str,
+    // Some query word only matched the name approximately, with `?fuzzy=true`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    fuzzy: bool,
+    #[serde(flatten)]
+    recipe: RecipeView,
+}
 
-    // Get list of recipe IDs
-    let ids_bytes = bucket.get("_recipe_ids").map_err(|e| format!("Failed to get IDs: {:?}", e))?;
+#[derive(Deserialize)]
+struct MakeableRequest {
+    ingredients: Vec<String>,
+}
 
-    let mut recipes = Vec::new();
+// Pantry matches, each list ranked by what's missing. `almost` holds recipes
+// short of at most MAX_ALMOST_MISSING required ingredients.
+#[derive(Serialize)]
+struct MakeableRecipes {
+    makeable: Vec<MakeableRecipe>,
+    almost: Vec<MakeableRecipe>,
+}
 
-    if let Some(data) = ids_bytes {
-        let ids_str = String::from_utf8(data).map_err(|e| format!("Invalid UTF-8: {:?}", e))?;
-        let ids: Vec<String> = serde_json::from_str(&ids_str).unwrap_or_default();
+// Ingredient names not covered by the pantry, split by whether the recipe
+// needs them
+#[derive(Serialize)]
+struct MakeableRecipe {
+    missing: Vec<String>,
+    missing_optional: Vec<String>,
+    #[serde(flatten)]
+    recipe: RecipeView,
+}
 
-        for id in ids {
-            if let Ok(Some(recipe)) = get_recipe(&id) {
-                recipes.push(recipe);
-            }
-        }
-    }
+// A recipe using the searched-for ingredient, with the ingredient lines that matched
+#[derive(Serialize)]
+struct IngredientMatch {
+    matched_ingredients: Vec<IngredientJson>,
+    #[serde(flatten)]
+    recipe: RecipeView,
+}
 
-    Ok(recipes)
+// A recipe recommended alongside another, with how much they have in common
+#[derive(Serialize)]
+struct RelatedRecipe {
+    shared_tags: usize,
+    shared_dietary_info: usize,
+    #[serde(flatten)]
+    recipe: RecipeView,
 }
 
-fn get_recipe(id: &str) -> Result<Option<RecipeJson>, String> {
-    let bucket = open("recipes").map_err(|e| format!("Failed to open bucket: {:?}", e))?;
+// A recipe from /popular with how often it has been fetched
+#[derive(Serialize)]
+struct PopularRecipe {
+    views: u64,
+    #[serde(flatten)]
+    recipe: RecipeView,
+}
 
-    let key = format!("recipe:{}", id);
-    let data = bucket.get(&key).map_err(|e| format!("Failed to get recipe: {:?}", e))?;
+// Rough calorie estimate for a recipe. Ingredients without calorie data
+// count as zero and are listed in `unknown`.
+#[derive(Serialize)]
+struct NutritionEstimate {
+    total_calories: f32,
+    per_serving: Option<f32>,
+    unknown: Vec<String>,
+}
 
-    match data {
-        Some(bytes) => {
-            let recipe = serde_json::from_slice(&bytes)
-                .map_err(|e| format!("Failed to deserialize: {:?}", e))?;
-            Ok(Some(recipe))
-        }
-        None => Ok(None),
-    }
+// A recipe, optionally scaled, with its step timeline for cooking along
+#[derive(Serialize)]
+struct CookMode {
+    recipe: RecipeView,
+    timeline: Timeline,
 }
 
-fn create_recipe(mut recipe: RecipeJson) -> Result<String, String> {
-    let bucket = open("recipes").map_err(|e| format!("Failed to open bucket: {:?}", e))?;
+// Steps in `order` with the minute each one starts at, assuming they run back to back
+#[derive(Serialize)]
+struct Timeline {
+    steps: Vec<TimelineStep>,
+    total_duration_mins: u32,
+}
 
-    // Generate ID if not provided
-    if recipe.id.is_empty() {
-        recipe.id = format!("recipe_{}", current_timestamp());
-    }
+#[derive(Serialize)]
+struct TimelineStep {
+    #[serde(flatten)]
+    step: StepJson,
+    start_at_mins: u32,
+}
 
-    // Set timestamps
-    let now = current_timestamp();
-    recipe.created_at = now;
-    recipe.updated_at = now;
+#[derive(Serialize)]
+struct TagCount {
+    tag: String,
+    count: usize,
+}
 
-    // Store recipe
-    let key = format!("recipe:{}", recipe.id);
-    let data = serde_json::to_vec(&recipe).map_err(|e| format!("Failed to serialize: {:?}", e))?;
-    bucket.set(&key, &data).map_err(|e| format!("Failed to store recipe: {:?}", e))?;
+#[derive(Serialize)]
+struct DietCount {
+    diet: String,
+    count: usize,
+}
 
-    // Update recipe IDs list
-    add_recipe_id(&bucket, &recipe.id)?;
+// What a create under an Idempotency-Key returned, kept so retries replay it
+#[derive(Serialize, Deserialize)]
+struct IdempotencyRecord {
+    recipe_id: String,
+    warnings: Vec<String>,
+    stored_at: u64,
+}
 
-    Ok(recipe.id.clone())
+// Entry in a recipe's version history. Version n is the recipe as it was
+// before its n-th update.
+#[derive(Serialize)]
+struct VersionSummary {
+    version: u64,
+    name: String,
+    updated_at: u64,
 }
 
-fn update_recipe(id: &str, recipe: RecipeJson) -> Result<(), String> {
-    let bucket = open("recipes").map_err(|e| format!("Failed to open bucket: {:?}", e))?;
+#[derive(Deserialize)]
+struct TagsRequest {
+    tags: Vec<String>,
+}
 
-    let key = format!("recipe:{}", id);
-    let data = serde_json::to_vec(&recipe).map_err(|e| format!("Failed to serialize: {:?}", e))?;
-    bucket.set(&key, &data).map_err(|e| format!("Failed to update recipe: {:?}", e))?;
+// Existing step `order` values, listed in their new sequence
+#[derive(Deserialize)]
+struct ReorderStepsRequest {
+    order: Vec<u8>,
+}
 
-    Ok(())
+// A named group of recipes, stored at `collection:{id}`. Members are held by
+// ID, so a recipe deleted later stays listed until it's removed.
+#[derive(Serialize, Deserialize)]
+struct CollectionJson {
+    id: String,
+    name: String,
+    recipe_ids: Vec<String>,
+    created_at: u64,
+    updated_at: u64,
 }
 
-fn delete_recipe(id: &str) -> Result<(), String> {
-    let bucket = open("recipes").map_err(|e| format!("Failed to open bucket: {:?}", e))?;
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CreateCollectionRequest {
+    name: String,
+    #[serde(default)]
+    recipe_ids: Vec<String>,
+}
 
-    let key = format!("recipe:{}", id);
-    bucket.delete(&key).map_err(|e| format!("Failed to delete recipe: {:?}", e))?;
+#[derive(Deserialize)]
+struct CollectionRecipeRequest {
+    recipe_id: String,
+}
 
-    // Remove from recipe IDs list
-    remove_recipe_id(&bucket, id)?;
+// Entry in GET /api/collections
+#[derive(Serialize)]
+struct CollectionSummary {
+    id: String,
+    name: String,
+    recipe_count: usize,
+    updated_at: u64,
+}
 
-    Ok(())
+// A collection with its members loaded. IDs that no longer name a live
+// recipe are listed in `missing`.
+#[derive(Serialize)]
+struct CollectionView {
+    id: String,
+    name: String,
+    recipes: Vec<RecipeView>,
+    missing: Vec<String>,
+    created_at: u64,
+    updated_at: u64,
 }
 
-fn add_recipe_id(bucket: &Bucket, id: &str) -> Result<(), String> {
-    let ids_bytes = bucket.get("_recipe_ids").map_err(|e| format!("Failed to get IDs: {:?}", e))?;
+#[derive(Deserialize)]
+struct BatchDeleteRequest {
+    ids: Vec<String>,
+}
 
-    let mut ids: Vec<String> = if let Some(data) = ids_bytes {
-        let ids_str = String::from_utf8(data).map_err(|e| format!("Invalid UTF-8: {:?}", e))?;
-        serde_json::from_str(&ids_str).unwrap_or_default()
-    } else {
-        Vec::new()
-    };
+#[derive(Deserialize)]
+struct ShoppingListRequest {
+    recipe_ids: Vec<String>,
+}
 
-    if !ids.contains(&id.to_string()) {
-        ids.push(id.to_string());
-        let ids_json = serde_json::to_vec(&ids).map_err(|e| format!("Failed to serialize IDs: {:?}", e))?;
-        bucket.set("_recipe_ids", &ids_json).map_err(|e| format!("Failed to store IDs: {:?}", e))?;
-    }
+// Ingredients merged across recipes. IDs that didn't resolve are listed in `missing`.
+#[derive(Serialize)]
+struct ShoppingList {
+    items: Vec<ShoppingListItem>,
+    missing: Vec<String>,
+}
 
-    Ok(())
+// One line per (name, unit); `optional` is only set if every recipe marks it optional
+#[derive(Serialize)]
+struct ShoppingListItem {
+    name: String,
+    amount: f32,
+    unit: String,
+    optional: bool,
 }
 
-fn remove_recipe_id(bucket: &Bucket, id: &str) -> Result<(), String> {
-    let ids_bytes = bucket.get("_recipe_ids").map_err(|e| format!("Failed to get IDs: {:?}", e))?;
+// Per-item outcome of a bulk create, import, or delete. `index` is the
+// item's position in the request and `status` the HTTP status it would
+// have got on its own.
+#[derive(Serialize)]
+struct BulkResult {
+    index: usize,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
 
-    if let Some(data) = ids_bytes {
-        let ids_str = String::from_utf8(data).map_err(|e| format!("Invalid UTF-8: {:?}", e))?;
-        let mut ids: Vec<String> = serde_json::from_str(&ids_str).unwrap_or_default();
+impl BulkResult {
+    fn succeeded(index: usize, status: u16, id: String) -> Self {
+        BulkResult { index, status, id: Some(id), error: None, warnings: Vec::new() }
+    }
 
-        ids.retain(|i| i != id);
+    fn failed(index: usize, status: u16, id: Option<String>, error: String) -> Self {
+        BulkResult { index, status, id, error: Some(error), warnings: Vec::new() }
+    }
 
-        let ids_json = serde_json::to_vec(&ids).map_err(|e| format!("Failed to serialize IDs: {:?}", e))?;
-        bucket.set("_recipe_ids", &ids_json).map_err(|e| format!("Failed to store IDs: {:?}", e))?;
+    fn from_error(index: usize, id: Option<String>, e: RecipeError) -> Self {
+        let status = e.status();
+        if status >= 500 {
+            log(Level::Error, "recipe-api", &format!("Error writing recipe {}: {}", index, e));
+            return BulkResult::failed(index, status, id, "internal error".to_string());
+        }
+        BulkResult::failed(index, status, id, e.to_string())
     }
+}
 
-    Ok(())
+// Overall status for a bulk response: `success` when every item succeeded
+// (including an empty batch), 400 when every item failed, 207 otherwise
+fn multi_status(results: &[BulkResult], success: u16) -> u16 {
+    let failures = results.iter().filter(|result| result.status >= 400).count();
+    match failures {
+        0 => success,
+        n if n == results.len() => 400,
+        _ => 207,
+    }
 }
 
-fn read_request_body(request: IncomingRequest) -> Result<Vec<u8>, String> {
-    let body = request.consume().map_err(|_| "Failed to consume body")?;
-    let stream = body.stream().map_err(|_| "Failed to get stream")?;
+// Counts for a streamed NDJSON import. Only failures are listed, and only
+// the first MAX_REPORTED_FAILURES of them.
+#[derive(Serialize, Default)]
+struct NdjsonImportSummary {
+    created: usize,
+    updated: usize,
+    skipped: usize,
+    failed: usize,
+    failures: Vec<BulkResult>,
+}
 
-    let mut result = Vec::new();
-    loop {
-        match stream.blocking_read(4096) {
-            Ok(chunk) => {
-                if chunk.is_empty() {
-                    break;
+impl NdjsonImportSummary {
+    fn record(&mut self, result: BulkResult, mode: &ImportMode) {
+        match (result.status, mode) {
+            (201, _) => self.created += 1,
+            (200, ImportMode::Merge) => self.skipped += 1,
+            (200, ImportMode::Replace) => self.updated += 1,
+            _ => {
+                self.failed += 1;
+                if self.failures.len() < MAX_REPORTED_FAILURES {
+                    self.failures.push(result);
                 }
-                result.extend_from_slice(&chunk);
             }
-            Err(_) => break,
         }
     }
 
-    Ok(result)
+    // Same rule as multi_status
+    fn status(&self) -> u16 {
+        match self.failed {
+            0 => 201,
+            _ if self.created + self.updated + self.skipped == 0 => 400,
+            _ => 207,
+        }
+    }
 }
 
-fn send_response(status: u16, body: &[u8], response_out: ResponseOutparam) {
-    let response = OutgoingResponse::new(Fields::new());
-    response.set_status_code(status).unwrap();
+// Outcome of rebuilding the ID index from the stored recipes
+#[derive(Serialize)]
+struct ReindexSummary {
+    added: Vec<String>,
+    removed: Vec<String>,
+    total: usize,
+}
 
-    let response_body = response.body().unwrap();
-    {
-        let stream = response_body.write().unwrap();
-        stream.blocking_write_and_flush(body).unwrap();
+// PATCH body: a field merge, or JSON Patch operations
+enum RecipePatch {
+    Merge(serde_json::Map<String, serde_json::Value>),
+    Operations(Vec<serde_json::Value>),
+}
+
+// Why a storage function failed. Handlers pass these to send_error, which
+// picks the status, so bad input, missing recipes and backend failures each
+// get their own instead of all being 500.
+#[derive(Debug)]
+enum RecipeError {
+    NotFound,
+    Validation(Vec<String>),
+    // A create named an ID that's already taken
+    Conflict,
+    // The keyvalue or blobstore backend failed or couldn't be reached
+    Storage(String),
+    // Data couldn't be encoded for storage, or what's stored couldn't be read back
+    Serialization(String),
+}
+
+impl RecipeError {
+    fn status(&self) -> u16 {
+        match self {
+            RecipeError::NotFound => 404,
+            RecipeError::Validation(_) => 400,
+            RecipeError::Conflict => 409,
+            RecipeError::Storage(_) => 503,
+            RecipeError::Serialization(_) => 500,
+        }
     }
+}
 
-    OutgoingBody::finish(response_body, None).unwrap();
-    ResponseOutparam::set(response_out, Ok(response));
+impl std::fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecipeError::NotFound => f.write_str("recipe not found"),
+            RecipeError::Validation(problems) => f.write_str(&problems.join("; ")),
+            RecipeError::Conflict => f.write_str("a recipe with this ID already exists"),
+            RecipeError::Storage(e) | RecipeError::Serialization(e) => f.write_str(e),
+        }
+    }
 }
 
-fn send_json_response(status: u16, body: &[u8], response_out: ResponseOutparam) {
-    let headers = Fields::new();
-    headers.set(&"content-type".to_string(), &[b"application/json".to_vec()]).unwrap();
+// Listing filters parsed from the query. Every filter that is set must hold.
+// Non-numeric values are ignored rather than rejected.
+struct RecipeFilter {
+    // Checked against the favorites index rather than the recipes
+    favorites_only: bool,
+    updated_since: Option<u64>,
+    // Repeated `diet=` / `tag=` values must all match, compared in kebab-case
+    diets: Vec<String>,
+    tags: Vec<String>,
+    // Recipes without an author never match
+    author: Option<String>,
+    max_total_time: Option<u32>,
+    max_prep_time: Option<u32>,
+    max_cook_time: Option<u32>,
+    // Only recipes with lint problems, each listed with its problems
+    incomplete_only: bool,
+    // Not a filter: adds `_links` to every listed recipe
+    with_links: bool,
+}
 
-    let response = OutgoingResponse::new(headers);
-    response.set_status_code(status).unwrap();
+impl RecipeFilter {
+    fn from_query(query: &Query) -> Self {
+        let minutes = |name: &str| query.get(name).and_then(|v| v.parse::<u32>().ok());
 
-    let response_body = response.body().unwrap();
-    {
-        let stream = response_body.write().unwrap();
-        stream.blocking_write_and_flush(body).unwrap();
+        RecipeFilter {
+            favorites_only: query.flag("favorites"),
+            updated_since: query.get("updated_since").and_then(|v| v.parse::<u64>().ok()),
+            diets: query.get_all("diet").to_vec(),
+            tags: query.get_all("tag").to_vec(),
+            author: query.get("author").map(str::to_string),
+            max_total_time: minutes("max_total_time"),
+            max_prep_time: minutes("max_prep_time"),
+            max_cook_time: minutes("max_cook_time"),
+            incomplete_only: query.flag("incomplete"),
+            with_links: query.flag("links"),
+        }
     }
 
-    OutgoingBody::finish(response_body, None).unwrap();
-    ResponseOutparam::set(response_out, Ok(response));
-}
+    // True if any filter needs the recipes loaded, not just their IDs
+    fn inspects_recipes(&self) -> bool {
+        self.updated_since.is_some()
+            || !self.diets.is_empty()
+            || !self.tags.is_empty()
+            || self.author.is_some()
+            || self.max_total_time.is_some()
+            || self.max_prep_time.is_some()
+            || self.max_cook_time.is_some()
+            || self.incomplete_only
+    }
 
-fn current_timestamp() -> u64 {
-    use wasi::clocks::wall_clock::now;
-    let duration = now();
+    fn matches(&self, recipe: &RecipeJson) -> bool {
+        let total_time = recipe.prep_time_mins.saturating_add(recipe.cook_time_mins);
+
+        self.updated_since.is_none_or(|since| recipe.updated_at > since)
+            && contains_all_labels(&recipe.dietary_info, &self.diets)
+            && contains_all_labels(&recipe.tags, &self.tags)
+            && self.author.as_ref().is_none_or(|author| {
+                recipe.author.as_ref().is_some_and(|a| a.eq_ignore_ascii_case(author))
+            })
+            && self.max_total_time.is_none_or(|max| total_time <= max)
+            && self.max_prep_time.is_none_or(|max| recipe.prep_time_mins <= max)
+            && self.max_cook_time.is_none_or(|max| recipe.cook_time_mins <= max)
+            && (!self.incomplete_only || !lint_recipe(recipe).is_empty())
+    }
+
+    // The listing form of a matched recipe
+    fn view(&self, recipe: RecipeJson) -> RecipeView {
+        let problems = self.incomplete_only.then(|| lint_recipe(&recipe));
+        let view = RecipeView { problems, ..RecipeView::from(recipe) };
+        if self.with_links {
+            view.with_links()
+        } else {
+            view
+        }
+    }
+}
+
+// Page selection parsed from `?page=` and `?per_page=`
+struct Pagination {
+    page: usize,
+    per_page: usize,
+}
+
+impl Pagination {
+    fn from_query(query: &Query) -> Self {
+        let page = query.get("page")
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&p| p >= 1)
+            .unwrap_or(1);
+
+        let per_page = query.get("per_page")
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&n| n >= 1)
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .min(MAX_PER_PAGE);
+
+        Pagination { page, per_page }
+    }
+
+    fn offset(&self) -> usize {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+}
+
+// Serialization format for recipe responses, negotiated from `Accept`
+#[derive(Clone, Copy)]
+enum ResponseFormat {
+    Json,
+    Yaml,
+    // One JSON object per line; a single value is a one-line document
+    Ndjson,
+    // Only single recipes have an HTML rendering; anything else falls back to JSON
+    Html,
+}
+
+impl ResponseFormat {
+    fn negotiate(headers: &Fields) -> Self {
+        header_value(headers, "accept")
+            .map(|accept| Self::from_accept(&accept))
+            .unwrap_or(ResponseFormat::Json)
+    }
+
+    // Pick the supported type with the highest q-value, preferring earlier
+    // entries on ties. Anything unrecognised, including `*/*`, means JSON.
+    fn from_accept(accept: &str) -> Self {
+        let mut best = (ResponseFormat::Json, 0.0_f32);
+
+        for range in accept.split(',') {
+            let mut params = range.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or("").to_ascii_lowercase();
+            let q = params
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let format = match media_type.as_str() {
+                "application/yaml" | "application/x-yaml" | "text/yaml" => ResponseFormat::Yaml,
+                "application/x-ndjson" => ResponseFormat::Ndjson,
+                "text/html" => ResponseFormat::Html,
+                "application/json" | "application/*" | "*/*" => ResponseFormat::Json,
+                _ => continue,
+            };
+            if q > best.1 {
+                best = (format, q);
+            }
+        }
+
+        best.0
+    }
+}
+
+// Listing sort order parsed from `?sort=` and `?order=`. `sort` takes a
+// comma-separated list of keys, each breaking ties left by the one before.
+struct SortOrder {
+    keys: Vec<(SortKey, bool)>,
+}
+
+#[derive(Clone, Copy)]
+enum SortKey {
+    Name,
+    CreatedAt,
+    UpdatedAt,
+    PrepTime,
+    CookTime,
+    Difficulty,
+}
+
+impl SortOrder {
+    // A `-` prefix sorts that key descending; other keys follow `order=desc`.
+    // Unknown keys are skipped, and if none are left the listing keeps its
+    // default order.
+    fn from_query(query: &Query) -> Option<Self> {
+        let default_descending = query.get("order") == Some("desc");
+
+        let keys: Vec<(SortKey, bool)> = query
+            .get("sort")?
+            .split(',')
+            .filter_map(|field| {
+                let field = field.trim();
+                let (name, descending) = match field.strip_prefix('-') {
+                    Some(name) => (name, true),
+                    None => (field, default_descending),
+                };
+                let key = match name {
+                    "name" => SortKey::Name,
+                    "created_at" => SortKey::CreatedAt,
+                    "updated_at" => SortKey::UpdatedAt,
+                    "prep_time_mins" => SortKey::PrepTime,
+                    "cook_time_mins" => SortKey::CookTime,
+                    "difficulty" => SortKey::Difficulty,
+                    _ => return None,
+                };
+                Some((key, descending))
+            })
+            .collect();
+
+        (!keys.is_empty()).then_some(SortOrder { keys })
+    }
+}
+
+// What an import does with recipes whose ID already exists
+enum ImportMode {
+    Merge,
+    Replace,
+}
+
+impl ImportMode {
+    // Defaults to merge; None for an unrecognized mode
+    fn from_query(query: &Query) -> Option<Self> {
+        match query.get("mode") {
+            None | Some("merge") => Some(ImportMode::Merge),
+            Some("replace") => Some(ImportMode::Replace),
+            Some(_) => None,
+        }
+    }
+}
+
+// How /api/recipes/grouped buckets recipes
+enum GroupBy {
+    Difficulty,
+    Tag,
+}
+
+impl GroupBy {
+    // `by` is required; None if it's missing or unrecognized
+    fn from_query(query: &Query) -> Option<Self> {
+        match query.get("by") {
+            Some("difficulty") => Some(GroupBy::Difficulty),
+            Some("tag") => Some(GroupBy::Tag),
+            _ => None,
+        }
+    }
+}
+
+impl Guest for Component {
+    fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
+        let path_with_query = request.path_with_query().unwrap_or("/".to_string());
+
+        REQUEST_CONTEXT.with(|ctx| *ctx.borrow_mut() = RequestContext::from_headers(&request.headers()));
+
+        log(Level::Info, "recipe-api", &format!("Request: {}", path_with_query));
+
+        // Parse path; only the first '?' separates the query
+        let (path, query_string) = path_with_query.split_once('?').unwrap_or((&path_with_query, ""));
+        let path = normalize_path(path);
+        let query = Query::parse(query_string);
+        REQUEST_CONTEXT.with(|ctx| ctx.borrow_mut().allow_step_gaps = query.flag("allow_gaps"));
+        let method = effective_method(&request, &query);
+        let _timer = RequestTimer::start(&method, &path);
+
+        if let Some(retry_after) = check_rate_limit(&request.headers()) {
+            let retry_after = retry_after.to_string();
+            send_response_with_headers(429, b"Too Many Requests", &[("retry-after", retry_after.as_str())], response_out);
+            return;
+        }
+
+        let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        let store = KeyValueStore;
+
+        // Route request
+        match method {
+            Method::Get => handle_get(&store, &path_segments, &query, &request.headers(), response_out),
+            Method::Post => handle_post(&store, &path_segments, &query, request, response_out),
+            Method::Put => handle_put(&store, &path_segments, &query, request, response_out),
+            Method::Patch => handle_patch(&store, &path_segments, request, response_out),
+            Method::Delete => handle_delete(&store, &path_segments, &query, &request.headers(), response_out),
+            Method::Head => handle_head(&store, &path_segments, response_out),
+            Method::Options => handle_options(&path_segments, response_out),
+            _ => send_no_route(&path_segments, response_out),
+        }
+    }
+}
+
+// "/api//recipes/" becomes "/api/recipes": one leading slash, repeated
+// slashes collapsed and trailing ones dropped, so logs, metrics and routing
+// all see the same path. An empty path is "/".
+fn normalize_path(path: &str) -> String {
+    let mut normalized = String::with_capacity(path.len() + 1);
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+// HTML forms can only send GET and POST, so a POST may name the method it
+// stands for in an `X-HTTP-Method-Override` header or `?_method=`, the header
+// winning if both are given. Only PUT, PATCH and DELETE can be requested;
+// any other value is ignored and the request stays a POST.
+fn effective_method(request: &IncomingRequest, query: &Query) -> Method {
+    let method = request.method();
+    if !matches!(method, Method::Post) {
+        return method;
+    }
+
+    let requested = header_value(&request.headers(), "x-http-method-override")
+        .or_else(|| query.get("_method").map(str::to_string))
+        .map(|name| name.trim().to_ascii_uppercase());
+
+    match requested.as_deref() {
+        Some("PUT") => Method::Put,
+        Some("PATCH") => Method::Patch,
+        Some("DELETE") => Method::Delete,
+        _ => method,
+    }
+}
+
+fn handle_get(store: &dyn RecipeStore, path: &[&str], query: &Query, headers: &Fields, response_out: ResponseOutparam) {
+    match path {
+        ["api", "collections"] => {
+            match list_collections() {
+                Ok(collections) => {
+                    send_json(200, &collections, response_out);
+                }
+                Err(e) => {
+                    send_error("Error listing collections", e, response_out);
+                }
+            }
+        }
+        ["api", "collections", id] => {
+            match load_collection(id) {
+                Ok(Some(collection)) => match expand_collection(store, collection) {
+                    Ok(view) => {
+                        send_json(200, &view, response_out);
+                    }
+                    Err(e) => {
+                        send_error("Error loading collection recipes", e, response_out);
+                    }
+                },
+                Ok(None) => {
+                    send_response(404, b"Collection not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error getting collection", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes"] => {
+            // List recipes one page at a time
+            let pagination = Pagination::from_query(query);
+            let sort = SortOrder::from_query(query);
+            let filter = RecipeFilter::from_query(query);
+
+            if query.flag("ids_only") {
+                match filtered_ids(store, &filter) {
+                    Ok(ids) => {
+                        let json = serde_json::json!({ "total": ids.len(), "ids": ids }).to_string();
+                        send_json_response(200, json.as_bytes(), response_out);
+                    }
+                    Err(e) => {
+                        send_error("Error listing recipe IDs", e, response_out);
+                    }
+                }
+                return;
+            }
+
+            let format = ResponseFormat::negotiate(headers);
+            if let ResponseFormat::Ndjson = format {
+                stream_recipe_listing(store, sort.as_ref(), &filter, requested_fields(query).as_deref(), response_out);
+                return;
+            }
+
+            match list_recipes(store, &pagination, sort.as_ref(), &filter) {
+                Ok(page) => {
+                    let fields = requested_fields(query);
+                    let envelope = query.flag("envelope");
+                    if fields.is_none() && !envelope {
+                        send_formatted(200, &page, format, &[], response_out);
+                        return;
+                    }
+
+                    let mut page = match to_json_value(&page) {
+                        Ok(page) => page,
+                        Err(e) => {
+                            send_error("Error listing recipes", e, response_out);
+                            return;
+                        }
+                    };
+                    if let Some(fields) = fields {
+                        // Project each recipe; the paging fields are kept whole
+                        if let Some(serde_json::Value::Array(recipes)) = page.get_mut("data") {
+                            recipes.iter_mut().for_each(|recipe| project_fields(recipe, &fields));
+                        }
+                    }
+                    if envelope {
+                        page = page_envelope(page);
+                    }
+                    send_formatted(200, &page, format, &[], response_out);
+                }
+                Err(e) => {
+                    send_error("Error listing recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "random"] => {
+            // Listing filters such as `tag=` narrow the pool to pick from
+            let filter = RecipeFilter::from_query(query);
+            match random_recipe(store, &filter) {
+                Ok(Some(recipe)) => {
+                    // Each request should get a fresh pick
+                    send_json_with_headers(200, &RecipeView::from(recipe), &[("cache-control", "no-store")], response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"No matching recipes", response_out);
+                }
+                Err(e) => {
+                    send_error("Error picking a recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "count"] => {
+            // Counts the index only, without loading any recipes
+            match count_recipes(store) {
+                Ok(count) => {
+                    let json = serde_json::json!({ "count": count }).to_string();
+                    send_json_response(200, json.as_bytes(), response_out);
+                }
+                Err(e) => {
+                    send_error("Error counting recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "tags"] => {
+            // Must come before the id arm so "tags" isn't treated as an ID
+            match load_all_recipes(store) {
+                Ok(recipes) => {
+                    let counts: Vec<TagCount> = tally(recipes.iter().flat_map(|recipe| &recipe.tags))
+                        .into_iter()
+                        .map(|(tag, count)| TagCount { tag, count })
+                        .collect();
+                    send_json(200, &counts, response_out);
+                }
+                Err(e) => {
+                    send_error("Error listing tags", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "diets"] => {
+            match load_all_recipes(store) {
+                Ok(recipes) => {
+                    let counts: Vec<DietCount> = tally(recipes.iter().flat_map(|recipe| &recipe.dietary_info))
+                        .into_iter()
+                        .map(|(diet, count)| DietCount { diet, count })
+                        .collect();
+                    send_json(200, &counts, response_out);
+                }
+                Err(e) => {
+                    send_error("Error listing diets", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "deleted"] => {
+            // Soft-deleted recipes waiting to be restored or purged
+            match store.deleted() {
+                Ok(recipes) => {
+                    let views: Vec<RecipeView> = recipes.into_iter().map(RecipeView::from).collect();
+                    send_json(200, &views, response_out);
+                }
+                Err(e) => {
+                    send_error("Error listing deleted recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "export"] => {
+            // Stored form (no computed fields) so the file can be imported back as-is
+            match load_all_recipes(store) {
+                Ok(recipes) => {
+                    send_json_with_headers(
+                        200,
+                        &recipes,
+                        &[("content-disposition", "attachment; filename=\"recipes.json\"")],
+                        response_out,
+                    );
+                }
+                Err(e) => {
+                    send_error("Error exporting recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "grouped"] => {
+            let Some(by) = GroupBy::from_query(query) else {
+                send_response(400, b"by must be 'difficulty' or 'tag'", response_out);
+                return;
+            };
+
+            match load_all_recipes(store) {
+                Ok(recipes) => {
+                    send_json(200, &group_recipes(recipes, &by), response_out);
+                }
+                Err(e) => {
+                    send_error("Error grouping recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "quick"] => {
+            // Must come before the id arm so "quick" isn't treated as an ID
+            let max = match query.get("max").map(|v| v.parse::<u32>()) {
+                None => DEFAULT_QUICK_MAX_MINS,
+                Some(Ok(max)) => max,
+                Some(Err(_)) => {
+                    send_response(400, b"max must be a number of minutes", response_out);
+                    return;
+                }
+            };
+
+            match quick_recipes(store, max) {
+                Ok(recipes) => {
+                    send_json(200, &recipes, response_out);
+                }
+                Err(e) => {
+                    send_error("Error listing quick recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "popular"] => {
+            let limit = query.get("limit")
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|&n| n >= 1)
+                .unwrap_or(DEFAULT_POPULAR_LIMIT)
+                .min(MAX_PER_PAGE);
+
+            match popular_recipes(store, limit) {
+                Ok(popular) => {
+                    send_json(200, &popular, response_out);
+                }
+                Err(e) => {
+                    send_error("Error loading popular recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "schema"] => {
+            // Built from the same constants and config as validate() so they can't drift
+            let json = schema::recipe_schema(&DIFFICULTY_LEVELS, max_time_mins()).to_string();
+            send_typed_response(200, json.as_bytes(), "application/schema+json", &[], response_out);
+        }
+        ["api", "recipes", "search"] => {
+            // Must come before the id arm so "search" isn't treated as an ID
+            let term = query.get("q").unwrap_or("").trim();
+            if term.is_empty() {
+                send_response(400, b"Missing search query", response_out);
+                return;
+            }
+
+            match search_recipes(store, term, query.flag("fuzzy")) {
+                Ok(results) => {
+                    send_json(200, &results, response_out);
+                }
+                Err(e) => {
+                    send_error("Error searching recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id] => {
+            // Get specific recipe, answering 304 if the client's copy is current
+            match store.get(id) {
+                Ok(Some(mut recipe)) => {
+                    record_view(store, id);
+
+                    let rating = served_rating(store, id);
+                    let etag = recipe_etag(&recipe, rating.as_ref());
+                    let modified_at = last_modified_at(&recipe, rating.as_ref());
+                    let last_modified = http_date::format(modified_at);
+                    let etag_header = [("etag", etag.as_str()), ("last-modified", last_modified.as_str())];
+
+                    if not_modified(headers, &etag, modified_at) {
+                        send_response_with_headers(304, b"", &etag_header, response_out);
+                        return;
+                    }
+
+                    // Expansions see the recipe as stored, before any unit conversion
+                    let embedded = match requested_expansions(query) {
+                        expansions if expansions.is_empty() => None,
+                        expansions => match embedded_resources(store, &recipe, &expansions) {
+                            Ok(embedded) => Some(embedded),
+                            Err(e) => {
+                                send_error("Error expanding recipe", e, response_out);
+                                return;
+                            }
+                        },
+                    };
+
+                    if query.get("units") == Some("imperial") {
+                        convert_to_imperial(&mut recipe);
+                    }
+
+                    let format = ResponseFormat::negotiate(headers);
+                    if let ResponseFormat::Html = format {
+                        // A fragment for server-side rendering; `fields` and `expand` don't apply
+                        let html = html::render_recipe(&recipe);
+                        let headers = [
+                            ("etag", etag.as_str()),
+                            ("last-modified", last_modified.as_str()),
+                            ("vary", "Accept"),
+                        ];
+                        send_typed_response(200, html.as_bytes(), "text/html; charset=utf-8", &headers, response_out);
+                        return;
+                    }
+
+                    let mut view = RecipeView::from(recipe);
+                    if query.flag("links") {
+                        view = view.with_links();
+                    }
+                    if query.flag("notes") {
+                        view = view.with_notes();
+                    }
+                    if let Some(rating) = rating {
+                        view = view.with_rating(rating);
+                    }
+                    let fields = requested_fields(query);
+                    let envelope = query.flag("envelope");
+                    if fields.is_none() && embedded.is_none() && !envelope {
+                        send_formatted(200, &view, format, &etag_header, response_out);
+                        return;
+                    }
+
+                    let mut view = match to_json_value(&view) {
+                        Ok(view) => view,
+                        Err(e) => {
+                            send_error("Error getting recipe", e, response_out);
+                            return;
+                        }
+                    };
+                    if let Some(fields) = fields {
+                        project_fields(&mut view, &fields);
+                    }
+                    if let (Some(embedded), serde_json::Value::Object(map)) = (embedded, &mut view) {
+                        map.insert("_embedded".to_string(), serde_json::Value::Object(embedded));
+                    }
+                    if envelope {
+                        view = serde_json::json!({ "data": view, "meta": { "etag": etag } });
+                    }
+                    send_formatted(200, &view, format, &etag_header, response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "difficulty", level] => {
+            // Unknown levels simply match nothing
+            match filter_recipes(store, |recipe| recipe.difficulty.eq_ignore_ascii_case(level)) {
+                Ok(recipes) => {
+                    let views: Vec<RecipeView> = recipes.into_iter().map(RecipeView::from).collect();
+                    send_json(200, &views, response_out);
+                }
+                Err(e) => {
+                    send_error("Error listing recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "by-ingredient", name] => {
+            let name = query::percent_decode(name);
+            if name.trim().is_empty() {
+                send_response(400, b"Missing ingredient name", response_out);
+                return;
+            }
+
+            match recipes_with_ingredient(store, name.trim()) {
+                Ok(matches) => {
+                    send_json(200, &matches, response_out);
+                }
+                Err(e) => {
+                    send_error("Error searching recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "related"] => {
+            // Invalid or zero limits fall back to the default
+            let limit = query.get("limit")
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|&n| n >= 1)
+                .unwrap_or(DEFAULT_RELATED_LIMIT)
+                .min(MAX_PER_PAGE);
+
+            match store.get(id) {
+                Ok(Some(recipe)) => match related_recipes(store, &recipe, limit) {
+                    Ok(related) => {
+                        send_json(200, &related, response_out);
+                    }
+                    Err(e) => {
+                        send_error("Error finding related recipes", e, response_out);
+                    }
+                },
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "scaled"] => {
+            let servings = match query.get("servings").and_then(|v| v.parse::<u16>().ok()) {
+                Some(servings) if servings > 0 => servings,
+                _ => {
+                    send_response(400, b"servings must be a number between 1 and 65535", response_out);
+                    return;
+                }
+            };
+
+            match store.get(id) {
+                Ok(Some(recipe)) => match scale_recipe(&recipe, servings) {
+                    Some(scaled) => {
+                        send_json(200, &RecipeView::from(scaled), response_out);
+                    }
+                    None => {
+                        send_response(400, b"Recipe has zero servings and cannot be scaled", response_out);
+                    }
+                },
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "cook"] => {
+            // Everything a kitchen display needs in one response
+            let servings = match query.get("servings").map(|v| v.parse::<u16>()) {
+                None => None,
+                Some(Ok(servings)) if servings > 0 => Some(servings),
+                Some(_) => {
+                    send_response(400, b"servings must be a number between 1 and 65535", response_out);
+                    return;
+                }
+            };
+
+            match store.get(id) {
+                Ok(Some(recipe)) => {
+                    let recipe = match servings {
+                        Some(servings) => match scale_recipe(&recipe, servings) {
+                            Some(scaled) => scaled,
+                            None => {
+                                send_response(400, b"Recipe has zero servings and cannot be scaled", response_out);
+                                return;
+                            }
+                        },
+                        None => recipe,
+                    };
+
+                    let timeline = build_timeline(&recipe);
+                    let cook = CookMode { recipe: RecipeView::from(recipe), timeline };
+                    send_json(200, &cook, response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "nutrition"] => {
+            match store.get(id) {
+                Ok(Some(recipe)) => {
+                    send_json(200, &estimate_nutrition(&recipe), response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "versions"] => {
+            match store.versions(id) {
+                Ok(versions) => {
+                    send_json(200, &versions, response_out);
+                }
+                Err(e) => {
+                    send_error("Error listing versions", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "versions", version] => {
+            let version = match version.parse::<u64>() {
+                Ok(version) => version,
+                Err(_) => {
+                    send_response(400, b"Version must be a number", response_out);
+                    return;
+                }
+            };
+
+            match store.version(id, version) {
+                Ok(Some(recipe)) => {
+                    send_json(200, &RecipeView::from(recipe), response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"Version not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error getting version", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "image"] => {
+            match load_image(id) {
+                Ok(Some(image)) => {
+                    let headers = response_headers();
+                    set_header(&headers, "content-type", image.content_type.as_bytes());
+                    set_header(&headers, "content-length", image.size.to_string().as_bytes());
+                    send_stream_response(200, headers, image.body, response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"Image not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error loading image", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "timeline"] => {
+            match store.get(id) {
+                Ok(Some(recipe)) => {
+                    send_json(200, &build_timeline(&recipe), response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                }
+            }
+        }
+        ["health"] | ["health", "live"] => {
+            // Liveness: the component is running, whatever its dependencies are doing
+            send_json_response(200, b"{\"status\":\"healthy\"}", response_out);
+        }
+        ["metrics"] => {
+            match open_bucket().and_then(|bucket| render_metrics(&bucket)) {
+                Ok(text) => {
+                    send_typed_response(200, text.as_bytes(), "text/plain; version=0.0.4", &[], response_out);
+                }
+                Err(e) => {
+                    send_error("Error reading metrics", e, response_out);
+                }
+            }
+        }
+        ["health", "ready"] => {
+            // Readiness: the recipe store is reachable
+            match store.ping() {
+                Ok(_) => {
+                    send_json_response(200, b"{\"status\":\"ready\"}", response_out);
+                }
+                Err(e) => {
+                    log(Level::Error, "recipe-api", &format!("Readiness check failed: {}", e));
+                    send_json_response(503, b"{\"status\":\"unavailable\"}", response_out);
+                }
+            }
+        }
+        _ => {
+            send_no_route(path, response_out);
+        }
+    }
+}
+
+fn handle_post(store: &dyn RecipeStore, path: &[&str], query: &Query, request: IncomingRequest, response_out: ResponseOutparam) {
+    let ndjson = media_type(&request.headers()).as_deref() == Some("application/x-ndjson");
+    let ndjson_import = ndjson && matches!(path, ["api", "recipes", "import"]);
+    if !ndjson_import && !has_json_content_type(&request.headers()) {
+        send_unsupported_media_type(response_out);
+        return;
+    }
+
+    match path {
+        ["api", "collections"] => {
+            let Some((request_body, response_out)) = read_json_body::<CreateCollectionRequest>(request, response_out) else {
+                return;
+            };
+
+            match create_collection(store, request_body) {
+                Ok(id) => {
+                    let location = format!("/api/collections/{}", id);
+                    let json = serde_json::json!({ "id": id }).to_string();
+                    send_json_response_with_headers(201, json.as_bytes(), &[("location", location.as_str())], response_out);
+                }
+                Err(e) => {
+                    send_error("Error creating collection", e, response_out);
+                }
+            }
+        }
+        ["api", "collections", id, "recipes"] => {
+            let Some((request_body, response_out)) = read_json_body::<CollectionRecipeRequest>(request, response_out) else {
+                return;
+            };
+            let recipe_id = request_body.recipe_id;
+
+            match add_to_collection(store, id, &recipe_id)
+                .and_then(|collection| collection.map(|collection| expand_collection(store, collection)).transpose())
+            {
+                Ok(Some(view)) => {
+                    send_json(200, &view, response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"Collection not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating collection", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "import"] => {
+            // Accepts the array produced by the export endpoint, or one
+            // recipe per line as NDJSON
+            let mode = match ImportMode::from_query(query) {
+                Some(mode) => mode,
+                None => {
+                    send_response(400, b"mode must be 'merge' or 'replace'", response_out);
+                    return;
+                }
+            };
+
+            if ndjson_import {
+                match with_index_batch(|| import_recipes_ndjson(store, request, mode)) {
+                    Ok(summary) => {
+                        send_json(summary.status(), &summary, response_out);
+                    }
+                    Err(e) => {
+                        send_error("Error importing recipes", e, response_out);
+                    }
+                }
+                return;
+            }
+
+            let Some((items, response_out)) = read_json_body::<Vec<serde_json::Value>>(request, response_out) else {
+                return;
+            };
+
+            match with_index_batch(|| import_recipes(store, items, mode)) {
+                Ok(results) => {
+                    send_json(multi_status(&results, 201), &results, response_out);
+                }
+                Err(e) => {
+                    send_error("Error importing recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "bulk"] => {
+            // Create many recipes, reporting each item's outcome
+            let Some((items, response_out)) = read_json_body::<Vec<serde_json::Value>>(request, response_out) else {
+                return;
+            };
+
+            match with_index_batch(|| create_recipes_bulk(store, items)) {
+                Ok(results) => {
+                    send_json(multi_status(&results, 201), &results, response_out);
+                }
+                Err(e) => {
+                    send_error("Error creating recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "batch-delete"] => {
+            let Some((request_body, response_out)) = read_json_body::<BatchDeleteRequest>(request, response_out) else {
+                return;
+            };
+            let ids = request_body.ids;
+
+            let hard = query.get("hard") == Some("true");
+            match with_index_batch(|| delete_recipes_batch(store, &ids, hard)) {
+                Ok(results) => {
+                    send_json(multi_status(&results, 200), &results, response_out);
+                }
+                Err(e) => {
+                    send_error("Error deleting recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "admin", "reindex"] => {
+            match admin_access(&request.headers()) {
+                AdminAccess::Granted => {}
+                AdminAccess::Disabled => {
+                    send_response(403, b"Admin endpoints are disabled", response_out);
+                    return;
+                }
+                AdminAccess::Denied => {
+                    send_response(401, b"Invalid admin token", response_out);
+                    return;
+                }
+            }
+
+            match reindex_recipes() {
+                Ok(summary) => {
+                    log(
+                        Level::Info,
+                        "recipe-api",
+                        &format!("Reindexed recipes: {} added, {} removed", summary.added.len(), summary.removed.len()),
+                    );
+                    send_json(200, &summary, response_out);
+                }
+                Err(e) => {
+                    send_error("Error reindexing recipes", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", "makeable"] => {
+            let Some((request_body, response_out)) = read_json_body::<MakeableRequest>(request, response_out) else {
+                return;
+            };
+            let pantry = request_body.ingredients;
+            if pantry.iter().all(|item| item.trim().is_empty()) {
+                send_response(400, b"ingredients must not be empty", response_out);
+                return;
+            }
+
+            match makeable_recipes(store, &pantry) {
+                Ok(recipes) => {
+                    send_json(200, &recipes, response_out);
+                }
+                Err(e) => {
+                    send_error("Error matching pantry", e, response_out);
+                }
+            }
+        }
+        ["api", "shopping-list"] => {
+            let Some((request_body, response_out)) = read_json_body::<ShoppingListRequest>(request, response_out) else {
+                return;
+            };
+
+            match build_shopping_list(store, &request_body.recipe_ids) {
+                Ok(list) => {
+                    send_json(200, &list, response_out);
+                }
+                Err(e) => {
+                    send_error("Error building shopping list", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "tags"] => {
+            // Append tags; they're normalized and deduplicated on store
+            let Some((request_body, response_out)) = read_json_body::<TagsRequest>(request, response_out) else {
+                return;
+            };
+            let tags = request_body.tags;
+
+            match edit_recipe(store, id, |recipe| {
+                recipe.tags = merge_tags(&recipe.tags, &tags);
+                true
+            }) {
+                Ok(recipe) => {
+                    send_json(200, &RecipeView::from(recipe), response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "steps", "reorder"] => {
+            let Some((request_body, response_out)) = read_json_body::<ReorderStepsRequest>(request, response_out) else {
+                return;
+            };
+            let order = request_body.order;
+
+            let mut problems = Vec::new();
+            match edit_recipe(store, id, |recipe| match reorder_steps(&mut recipe.instructions, &order) {
+                Ok(()) => true,
+                Err(found) => {
+                    problems = found;
+                    false
+                }
+            }) {
+                Ok(_) if !problems.is_empty() => {
+                    send_validation_errors(&problems, response_out);
+                }
+                Ok(recipe) => {
+                    send_json(200, &RecipeView::from(recipe), response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "ratings"] => {
+            let Some((request_body, response_out)) = read_json_body::<RatingRequest>(request, response_out) else {
+                return;
+            };
+            let rating = request_body.rating;
+            if !(1..=5).contains(&rating) {
+                send_response(400, b"rating must be between 1 and 5", response_out);
+                return;
+            }
+
+            match rate_recipe(store, id, rating as u64) {
+                Ok(summary) => {
+                    send_json(200, &summary, response_out);
+                }
+                Err(e) => {
+                    send_error("Error rating recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "favorite"] => {
+            match store.set_favorite(id, true) {
+                Ok(()) => {
+                    send_json_response(200, b"{\"favorite\":true}", response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating favorites", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "restore"] => {
+            match store.restore(id) {
+                Ok(RestoreOutcome::Restored(recipe)) => {
+                    send_json(200, &RecipeView::from(*recipe), response_out);
+                }
+                Ok(RestoreOutcome::NotDeleted) => {
+                    send_response(404, b"Deleted recipe not found", response_out);
+                }
+                Ok(RestoreOutcome::Conflict) => {
+                    send_response(409, b"A recipe with this ID already exists", response_out);
+                }
+                Err(e) => {
+                    send_error("Error restoring recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "scale-copy"] => {
+            // A stored copy of /scaled, e.g. a batch-cooking variant
+            let servings = match query.get("servings").and_then(|v| v.parse::<u16>().ok()) {
+                Some(servings) if servings > 0 => servings,
+                _ => {
+                    send_response(400, b"servings must be a number between 1 and 65535", response_out);
+                    return;
+                }
+            };
+
+            let mut copy = match store.get(id) {
+                Ok(Some(recipe)) => match scale_recipe(&recipe, servings) {
+                    Some(scaled) => scaled,
+                    None => {
+                        send_response(400, b"Recipe has zero servings and cannot be scaled", response_out);
+                        return;
+                    }
+                },
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                    return;
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                    return;
+                }
+            };
+            copy.id = String::new();
+            copy.name = format!("{} (x{})", copy.name, servings);
+
+            match store.create(copy) {
+                Ok(new_id) => {
+                    send_created(&new_id, &[], response_out);
+                }
+                Err(e) => {
+                    send_error("Error creating recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "duplicate"] => {
+            // Copy gets a fresh ID and timestamps; favorites stay with the original
+            let mut copy = match store.get(id) {
+                Ok(Some(recipe)) => recipe,
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                    return;
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                    return;
+                }
+            };
+            copy.id = String::new();
+            copy.name = format!("{} (copy)", copy.name);
+
+            match store.create(copy) {
+                Ok(new_id) => {
+                    send_created(&new_id, &[], response_out);
+                }
+                Err(e) => {
+                    send_error("Error creating recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes"] => {
+            // Create new recipe. A retry carrying a known Idempotency-Key gets
+            // the original response instead of a duplicate.
+            let idempotency_key = header_value(&request.headers(), "idempotency-key");
+            // Fills in `author` when the body leaves it out
+            let author = header_value(&request.headers(), "x-author")
+                .map(|author| author.trim().to_string())
+                .filter(|author| !author.is_empty() && author.len() <= MAX_IDENTIFIER_LEN);
+            if let Some(key) = idempotency_key.as_deref().filter(|_| !query.flag("dry_run")) {
+                if !is_safe_identifier(key) {
+                    send_response(400, b"Invalid Idempotency-Key", response_out);
+                    return;
+                }
+
+                match find_idempotent_create(key) {
+                    Ok(Some(record)) => {
+                        send_created(&record.recipe_id, &record.warnings, response_out);
+                        return;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        send_error("Error checking idempotency key", e, response_out);
+                        return;
+                    }
+                }
+            }
+
+            let Some((mut recipe_json, response_out)) = read_recipe_body(request, response_out) else {
+                return;
+            };
+            if recipe_json.author.is_none() {
+                recipe_json.author = author;
+            }
+            if query.flag("dry_run") {
+                send_dry_run(preview_create(store, recipe_json), response_out);
+                return;
+            }
+
+            let warnings = unit_warnings(&recipe_json);
+            match store.create(recipe_json) {
+                Ok(id) => {
+                    if let Some(key) = &idempotency_key {
+                        // The recipe exists now; losing the record only risks a retry duplicating it
+                        if let Err(e) = record_idempotent_create(key, &id, &warnings) {
+                            log(Level::Warn, "recipe-api", &format!("Failed to record idempotency key: {}", e));
+                        }
+                    }
+                    send_created(&id, &warnings, response_out);
+                }
+                Err(e) => {
+                    send_error("Error creating recipe", e, response_out);
+                }
+            }
+        }
+        _ => {
+            send_no_route(path, response_out);
+        }
+    }
+}
+
+fn handle_put(store: &dyn RecipeStore, path: &[&str], query: &Query, request: IncomingRequest, response_out: ResponseOutparam) {
+    // Images carry their own content type, checked against IMAGE_CONTENT_TYPES
+    if let ["api", "recipes", id, "image"] = path {
+        put_recipe_image(store, id, request, response_out);
+        return;
+    }
+
+    if !has_json_content_type(&request.headers()) {
+        send_unsupported_media_type(response_out);
+        return;
+    }
+
+    match path {
+        ["api", "recipes", id] => {
+            // Update recipe, optionally guarded by If-Match
+            let if_match = header_value(&request.headers(), "if-match");
+
+            let Some((mut recipe_json, response_out)) = read_recipe_body(request, response_out) else {
+                return;
+            };
+            recipe_json.id = id.to_string();
+
+            if let Some(expected) = &if_match {
+                match store.get(id) {
+                    Ok(current) => {
+                        let current_etag = current.as_ref().map(|recipe| recipe_etag(recipe, served_rating(store, id).as_ref()));
+                        if !current_etag.as_deref().is_some_and(|tag| etag_matches(expected, tag)) {
+                            send_precondition_failed(current_etag.as_deref(), response_out);
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        send_error("Error getting recipe", e, response_out);
+                        return;
+                    }
+                }
+            }
+
+            if query.flag("dry_run") {
+                send_dry_run(preview_upsert(store, id, recipe_json), response_out);
+                return;
+            }
+
+            match upsert_recipe(store, id, recipe_json) {
+                Ok((stored, created)) => {
+                    let etag = recipe_etag(&stored, served_rating(store, id).as_ref());
+                    let location = format!("/api/recipes/{}", id);
+                    let (status, outcome) = if created { (201, "created") } else { (200, "updated") };
+
+                    let mut body = serde_json::json!({ "status": outcome });
+                    let warnings = unit_warnings(&stored);
+                    if !warnings.is_empty() {
+                        body["warnings"] = serde_json::json!(warnings);
+                    }
+
+                    let mut headers = vec![("etag", etag.as_str())];
+                    if created {
+                        headers.push(("location", location.as_str()));
+                    }
+                    send_json_response_with_headers(status, body.to_string().as_bytes(), &headers, response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating recipe", e, response_out);
+                }
+            }
+        }
+        _ => {
+            send_no_route(path, response_out);
+        }
+    }
+}
+
+fn handle_patch(store: &dyn RecipeStore, path: &[&str], request: IncomingRequest, response_out: ResponseOutparam) {
+    match path {
+        ["api", "recipes", id] => {
+            // A JSON object merges fields, keeping any absent from the body;
+            // `application/json-patch+json` takes an RFC 6902 operation array
+            let is_json_patch = media_type(&request.headers()).as_deref() == Some("application/json-patch+json");
+
+            let Some((body, response_out)) = read_json_body::<serde_json::Value>(request, response_out) else {
+                return;
+            };
+            let patch = match body {
+                serde_json::Value::Array(operations) if is_json_patch => RecipePatch::Operations(operations),
+                serde_json::Value::Object(fields) if !is_json_patch => RecipePatch::Merge(fields),
+                _ if is_json_patch => {
+                    send_response(400, b"JSON Patch body must be an array of operations", response_out);
+                    return;
+                }
+                _ => {
+                    send_response(400, b"Patch body must be a JSON object", response_out);
+                    return;
+                }
+            };
+
+            let existing = match store.get(id) {
+                Ok(Some(recipe)) => recipe,
+                Ok(None) => {
+                    send_response(404, b"Recipe not found", response_out);
+                    return;
+                }
+                Err(e) => {
+                    send_error("Error getting recipe", e, response_out);
+                    return;
+                }
+            };
+
+            let patched = match apply_patch(&existing, patch) {
+                Ok(recipe) => recipe,
+                Err(json_patch::PatchError::Invalid(e)) => {
+                    send_response(400, e.as_bytes(), response_out);
+                    return;
+                }
+                Err(json_patch::PatchError::TestFailed(e)) => {
+                    send_response(409, e.as_bytes(), response_out);
+                    return;
+                }
+            };
+
+            match store.update(id, patched) {
+                Ok(stored) => {
+                    send_json(200, &RecipeView::from(stored), response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating recipe", e, response_out);
+                }
+            }
+        }
+        _ => {
+            send_no_route(path, response_out);
+        }
+    }
+}
+
+fn handle_head(store: &dyn RecipeStore, path: &[&str], response_out: ResponseOutparam) {
+    // Same status and headers as GET, but never a body
+    match path {
+        ["api", "recipes", id] => {
+            match store.get(id) {
+                Ok(Some(recipe)) => {
+                    let rating = served_rating(store, id);
+                    let etag = recipe_etag(&recipe, rating.as_ref());
+                    let last_modified = http_date::format(last_modified_at(&recipe, rating.as_ref()));
+                    let headers = [("etag", etag.as_str()), ("last-modified", last_modified.as_str())];
+                    send_json_response_with_headers(200, b"", &headers, response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"", response_out);
+                }
+                Err(e) => {
+                    log(Level::Error, "recipe-api", &format!("Error getting recipe: {}", e));
+                    send_response(e.status(), b"", response_out);
+                }
+            }
+        }
+        _ => match allowed_methods(path) {
+            Some(allow) => send_response_with_headers(405, b"", &[("allow", allow)], response_out),
+            None => send_response(404, b"", response_out),
+        },
+    }
+}
+
+fn handle_delete(store: &dyn RecipeStore, path: &[&str], query: &Query, headers: &Fields, response_out: ResponseOutparam) {
+    match path {
+        ["api", "collections", id] => {
+            match delete_collection(id) {
+                Ok(true) => {
+                    send_response(204, b"", response_out);
+                }
+                Ok(false) => {
+                    send_response(404, b"Collection not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error deleting collection", e, response_out);
+                }
+            }
+        }
+        ["api", "collections", id, "recipes", recipe_id] => {
+            match remove_from_collection(id, recipe_id) {
+                Ok(Some(true)) => {
+                    send_response(204, b"", response_out);
+                }
+                Ok(Some(false)) => {
+                    send_response(404, b"Recipe not in collection", response_out);
+                }
+                Ok(None) => {
+                    send_response(404, b"Collection not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating collection", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "favorite"] => {
+            match store.set_favorite(id, false) {
+                Ok(()) => {
+                    send_json_response(200, b"{\"favorite\":false}", response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating favorites", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id, "tags", tag] => {
+            // Tags may contain spaces, so the segment arrives URL-encoded
+            let tag = kebab_case(&query::percent_decode(tag));
+            let mut removed = false;
+            match edit_recipe(store, id, |recipe| {
+                let before = recipe.tags.len();
+                recipe.tags.retain(|t| kebab_case(t) != tag);
+                removed = recipe.tags.len() != before;
+                removed
+            }) {
+                Ok(recipe) if removed => {
+                    send_json(200, &RecipeView::from(recipe), response_out);
+                }
+                Ok(_) => {
+                    send_response(404, b"Tag not found", response_out);
+                }
+                Err(e) => {
+                    send_error("Error updating recipe", e, response_out);
+                }
+            }
+        }
+        ["api", "recipes", id] => {
+            // With If-Match, only delete the version the client last saw
+            if let Some(expected) = header_value(headers, "if-match") {
+                match store.get(id) {
+                    Ok(Some(current)) => {
+                        let current_etag = recipe_etag(&current, served_rating(store, id).as_ref());
+                        if !etag_matches(&expected, &current_etag) {
+                            send_precondition_failed(Some(&current_etag), response_out);
+                            return;
+                        }
+                    }
+                    Ok(None) => {
+                        send_response(404, b"Recipe not found", response_out);
+                        return;
+                    }
+                    Err(e) => {
+                        send_error("Error getting recipe", e, response_out);
+                        return;
+                    }
+                }
+            }
+
+            let hard = query.get("hard") == Some("true");
+            match store.delete(id, hard) {
+                Ok(_) => {
+                    send_response(204, b"", response_out);
+                }
+                Err(e) => {
+                    send_error("Error deleting recipe", e, response_out);
+                }
+            }
+        }
+        _ => {
+            send_no_route(path, response_out);
+        }
+    }
+}
+
+fn list_recipes(
+    store: &dyn RecipeStore,
+    pagination: &Pagination,
+    sort: Option<&SortOrder>,
+    filter: &RecipeFilter,
+) -> Result<RecipePage, RecipeError> {
+    let ids = listed_ids(store, filter)?;
+
+    let (total, data) = if sort.is_some() || filter.inspects_recipes() {
+        // Filtering and sorting need every recipe loaded before the page can be sliced
+        let mut recipes = load_recipes(store, &ids);
+        recipes.retain(|recipe| filter.matches(recipe));
+        if let Some(sort) = sort {
+            sort_recipes(&mut recipes, sort);
+        }
+
+        let total = recipes.len();
+        let data = recipes
+            .into_iter()
+            .skip(pagination.offset())
+            .take(pagination.per_page)
+            .map(|recipe| filter.view(recipe))
+            .collect();
+        (total, data)
+    } else {
+        // Otherwise slice the ID list first so only the requested page is deserialized
+        let total = ids.len();
+        let page_ids: Vec<String> = ids
+            .into_iter()
+            .skip(pagination.offset())
+            .take(pagination.per_page)
+            .collect();
+        (total, load_recipes(store, &page_ids).into_iter().map(RecipeView::from).collect())
+    };
+
+    Ok(RecipePage {
+        data,
+        total,
+        page: pagination.page,
+        per_page: pagination.per_page,
+    })
+}
+
+// Every live recipe ID, narrowed to favorites if the filter asks
+fn listed_ids(store: &dyn RecipeStore, filter: &RecipeFilter) -> Result<Vec<String>, RecipeError> {
+    let mut ids = store.list()?;
+    if filter.favorites_only {
+        let favorites: HashSet<String> = store.favorites()?.into_iter().collect();
+        ids.retain(|id| favorites.contains(id));
+    }
+
+    Ok(ids)
+}
+
+// IDs for `?ids_only=true`, in listing order and unpaginated. Straight from
+// store.list() unless a filter has to look inside the recipes, e.g.
+// `updated_since`.
+fn filtered_ids(store: &dyn RecipeStore, filter: &RecipeFilter) -> Result<Vec<String>, RecipeError> {
+    let ids = listed_ids(store, filter)?;
+    if !filter.inspects_recipes() {
+        return Ok(ids);
+    }
+
+    Ok(load_recipes(store, &ids)
+        .into_iter()
+        .filter(|recipe| filter.matches(recipe))
+        .map(|recipe| recipe.id)
+        .collect())
+}
+
+// The whole listing as NDJSON, written to the response a line at a time.
+// Pagination doesn't apply, since this is meant for exports. Without sort or
+// filters only one recipe is held at a time; otherwise they're all loaded
+// first, as for list_recipes, but still serialized one by one.
+fn stream_recipe_listing(
+    store: &dyn RecipeStore,
+    sort: Option<&SortOrder>,
+    filter: &RecipeFilter,
+    fields: Option<&[String]>,
+    response_out: ResponseOutparam,
+) {
+    let ids = match listed_ids(store, filter) {
+        Ok(ids) => ids,
+        Err(e) => {
+            send_error("Error listing recipes", e, response_out);
+            return;
+        }
+    };
+
+    let recipes: Box<dyn Iterator<Item = RecipeJson>> = if sort.is_some() || filter.inspects_recipes() {
+        let mut recipes = load_recipes(store, &ids);
+        recipes.retain(|recipe| filter.matches(recipe));
+        if let Some(sort) = sort {
+            sort_recipes(&mut recipes, sort);
+        }
+        Box::new(recipes.into_iter())
+    } else {
+        Box::new(ids.into_iter().filter_map(|id| store.get(&id).ok().flatten()))
+    };
+
+    let headers = response_headers();
+    set_header(&headers, "content-type", b"application/x-ndjson");
+    set_header(&headers, "vary", b"Accept");
+    let Some(body) = begin_stream_response(200, headers, response_out) else {
+        return;
+    };
+
+    match body.write() {
+        Ok(stream) => {
+            for recipe in recipes {
+                // The status is already sent, so a bad recipe can only end the stream early
+                let mut line = match to_json_value(&filter.view(recipe)) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        log(Level::Error, "recipe-api", &format!("Error streaming recipes: {}", e));
+                        break;
+                    }
+                };
+                if let Some(fields) = fields {
+                    project_fields(&mut line, fields);
+                }
+                let line = line.to_string() + "\n";
+
+                // A failed write means the client went away
+                if write_all(&stream, line.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        }
+        Err(_) => log(Level::Error, "recipe-api", "Failed to open response stream"),
+    }
+
+    finish_stream_response(body);
+}
+
+// A uniformly chosen recipe among those matching `filter`, or None if none do.
+// Without recipe-level filters only the chosen recipe is loaded.
+fn random_recipe(store: &dyn RecipeStore, filter: &RecipeFilter) -> Result<Option<RecipeJson>, RecipeError> {
+    let ids = listed_ids(store, filter)?;
+
+    if filter.inspects_recipes() {
+        let mut recipes = load_recipes(store, &ids);
+        recipes.retain(|recipe| filter.matches(recipe));
+        let Some(index) = random_index(recipes.len()) else {
+            return Ok(None);
+        };
+        Ok(Some(recipes.swap_remove(index)))
+    } else {
+        match random_index(ids.len()) {
+            Some(index) => store.get(&ids[index]),
+            None => Ok(None),
+        }
+    }
+}
+
+// Modulo bias is negligible for any realistic number of recipes
+fn random_index(len: usize) -> Option<usize> {
+    (len > 0).then(|| (wasi::random::random::get_random_u64() % len as u64) as usize)
+}
+
+fn count_recipes(store: &dyn RecipeStore) -> Result<usize, RecipeError> {
+    Ok(store.list()?.len())
+}
+
+fn load_all_recipes(store: &dyn RecipeStore) -> Result<Vec<RecipeJson>, RecipeError> {
+    let ids = store.list()?;
+
+    Ok(load_recipes(store, &ids))
+}
+
+// Load every recipe and keep those matching `predicate`, in listing order
+fn filter_recipes<F>(store: &dyn RecipeStore, predicate: F) -> Result<Vec<RecipeJson>, RecipeError>
+where
+    F: Fn(&RecipeJson) -> bool,
+{
+    Ok(load_all_recipes(store)?.into_iter().filter(|recipe| predicate(recipe)).collect())
+}
+
+// Recipes whose prep plus cook time is at most `max` minutes, quickest
+// first. Ties keep listing order.
+fn quick_recipes(store: &dyn RecipeStore, max: u32) -> Result<Vec<RecipeView>, RecipeError> {
+    let mut recipes: Vec<RecipeView> = load_all_recipes(store)?
+        .into_iter()
+        .map(RecipeView::from)
+        .filter(|view| view.total_time_mins <= max)
+        .collect();
+    recipes.sort_by_key(|view| view.total_time_mins);
+    Ok(recipes)
+}
+
+// Load recipes in the given order, skipping any that are missing or unreadable
+fn load_recipes(store: &dyn RecipeStore, ids: &[String]) -> Vec<RecipeJson> {
+    ids.iter().filter_map(|id| store.get(id).ok().flatten()).collect()
+}
+
+// Compared in kebab-case, so `tag=Quick Dinner` finds "quick-dinner" and
+// recipes stored before labels were normalized still match
+fn contains_all_labels(values: &[String], wanted: &[String]) -> bool {
+    wanted.iter().map(|w| kebab_case(w)).all(|w| values.iter().any(|v| kebab_case(v) == w))
+}
+
+// Recipes keyed by lowercased difficulty or kebab-cased tag, in listing order
+// within each group. Every known difficulty has a key even when empty. By
+// tag, a recipe appears under each of its tags once, matched the way stored
+// tags are normalized, and recipes without tags under `_untagged`.
+fn group_recipes(recipes: Vec<RecipeJson>, by: &GroupBy) -> BTreeMap<String, Vec<RecipeView>> {
+    let mut groups: BTreeMap<String, Vec<RecipeView>> = BTreeMap::new();
+
+    match by {
+        GroupBy::Difficulty => {
+            for level in DIFFICULTY_LEVELS {
+                groups.insert(level.to_string(), Vec::new());
+            }
+            for recipe in recipes {
+                groups.entry(recipe.difficulty.to_lowercase()).or_default().push(recipe.into());
+            }
+        }
+        GroupBy::Tag => {
+            for recipe in recipes {
+                let mut keys = normalize_labels(&recipe.tags);
+                if keys.is_empty() {
+                    keys.push("_untagged".to_string());
+                }
+
+                for key in keys {
+                    groups.entry(key).or_default().push(RecipeView::from(recipe.clone()));
+                }
+            }
+        }
+    }
+
+    groups
+}
+
+// Count values case-insensitively, labelling each with its most common
+// casing (first seen wins a tie). Sorted by count descending, then name.
+fn tally<'a>(values: impl Iterator<Item = &'a String>) -> Vec<(String, usize)> {
+    // (lowercased key, casings seen with their counts)
+    let mut groups: Vec<(String, Vec<(&'a str, usize)>)> = Vec::new();
+
+    for value in values {
+        let key = value.to_lowercase();
+        let index = match groups.iter().position(|(k, _)| *k == key) {
+            Some(index) => index,
+            None => {
+                groups.push((key, Vec::new()));
+                groups.len() - 1
+            }
+        };
+        let casings = &mut groups[index].1;
+        match casings.iter_mut().find(|(casing, _)| *casing == value.as_str()) {
+            Some((_, count)) => *count += 1,
+            None => casings.push((value.as_str(), 1)),
+        }
+    }
+
+    let mut counts: Vec<(String, String, usize)> = groups
+        .into_iter()
+        .map(|(key, casings)| {
+            let total = casings.iter().map(|(_, count)| count).sum();
+            // max_by_key keeps the last maximum, so reverse to favour the first seen
+            let display = casings.iter().rev().max_by_key(|(_, count)| *count).map(|(casing, _)| *casing).unwrap_or("");
+            (key, display.to_string(), total)
+        })
+        .collect();
+
+    counts.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+
+    counts.into_iter().map(|(_, display, count)| (display, count)).collect()
+}
+
+fn sort_recipes(recipes: &mut [RecipeJson], sort: &SortOrder) {
+    // sort_by is stable, so full ties keep insertion order in either direction
+    recipes.sort_by(|a, b| {
+        sort.keys
+            .iter()
+            .map(|&(key, descending)| {
+                let ordering = compare_by(key, a, b);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+fn compare_by(key: SortKey, a: &RecipeJson, b: &RecipeJson) -> std::cmp::Ordering {
+    match key {
+        SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        SortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+        SortKey::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+        SortKey::PrepTime => a.prep_time_mins.cmp(&b.prep_time_mins),
+        SortKey::CookTime => a.cook_time_mins.cmp(&b.cook_time_mins),
+        SortKey::Difficulty => difficulty_rank(&a.difficulty).cmp(&difficulty_rank(&b.difficulty)),
+    }
+}
+
+// Position in DIFFICULTY_LEVELS, so easy < medium < hard; anything else sorts last
+fn difficulty_rank(difficulty: &str) -> usize {
+    DIFFICULTY_LEVELS
+        .iter()
+        .position(|level| level.eq_ignore_ascii_case(difficulty))
+        .unwrap_or(DIFFICULTY_LEVELS.len())
+}
+
+// Any-term search: each query word scores its field's weight for every
+// field it appears in, and a recipe's score is the sum over words. Highest
+// score first; ties keep listing order.
+//
+// With `fuzzy`, a word found nowhere may still match a word of the name
+// within a few typos. That adds nothing to the score, so any exact match
+// outranks it; among equal scores, more fuzzy words and then fewer edits
+// come first.
+fn search_recipes(store: &dyn RecipeStore, query: &str, fuzzy: bool) -> Result<Vec<SearchResult>, RecipeError> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in query.split_whitespace().map(plural::normalize) {
+        if !term.is_empty() && !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+
+    let mut hits: Vec<(SearchScore, SearchResult)> = load_all_recipes(store)?
+        .into_iter()
+        .filter_map(|recipe| {
+            let score = score_recipe(&recipe, &terms, fuzzy)?;
+            let result = SearchResult {
+                score: score.score,
+                matched_field: score.matched_field,
+                fuzzy: score.fuzzy_terms > 0,
+                recipe: recipe.into(),
+            };
+            Some((score, result))
+        })
+        .collect();
+
+    // Stable sort keeps listing order among equal scores
+    hits.sort_by_key(|(score, _)| score.rank());
+
+    Ok(hits.into_iter().map(|(_, hit)| hit).collect())
+}
+
+struct SearchScore {
+    score: u32,
+    matched_field: &'static str,
+    // Words matched only approximately, and their total edit distance
+    fuzzy_terms: usize,
+    distance: usize,
+}
+
+impl SearchScore {
+    // Sort key: higher scores first, so exact and substring matches rank
+    // above fuzzy-only ones; then more words matched, then fewer edits
+    fn rank(&self) -> (std::cmp::Reverse<u32>, std::cmp::Reverse<usize>, usize) {
+        (std::cmp::Reverse(self.score), std::cmp::Reverse(self.fuzzy_terms), self.distance)
+    }
+}
+
+// Score and best-matching field for a recipe, or None if no term matches.
+// `terms` must already have been through `plural::normalize`.
+fn score_recipe(recipe: &RecipeJson, terms: &[String], fuzzy: bool) -> Option<SearchScore> {
+    // Highest weight first, so the lowest matching index is the best field
+    let fields: [(&'static str, u32, Vec<String>); 5] = [
+        ("name", 8, vec![plural::normalize(&recipe.name)]),
+        ("description", 4, recipe.description.iter().map(|d| plural::normalize(d)).collect()),
+        ("ingredients", 2, recipe.ingredients.iter().map(|i| plural::normalize(&i.name)).collect()),
+        ("tags", 1, recipe.tags.iter().map(|t| plural::normalize(t)).collect()),
+        ("notes", 1, recipe.ingredients.iter().filter_map(|i| i.notes.as_deref()).map(plural::normalize).collect()),
+    ];
+
+    let mut score = 0;
+    let mut best: Option<usize> = None;
+    let mut fuzzy_terms = 0;
+    let mut distance = 0;
+    for term in terms {
+        let mut matched = false;
+        for (index, (_, weight, texts)) in fields.iter().enumerate() {
+            if texts.iter().any(|text| text.contains(term.as_str())) {
+                score += weight;
+                best = Some(best.map_or(index, |b| b.min(index)));
+                matched = true;
+            }
+        }
+
+        if !matched && fuzzy {
+            if let Some(edits) = fuzzy::closest_word(term, &fields[0].2[0]) {
+                fuzzy_terms += 1;
+                distance += edits;
+                best = best.or(Some(0));
+            }
+        }
+    }
+
+    best.map(|index| SearchScore { score, matched_field: fields[index].0, fuzzy_terms, distance })
+}
+
+// Recipes the pantry covers, ignoring amounts. A pantry item covers an
+// ingredient when its words appear whole and in order in the ingredient's
+// name, ignoring case and plurals, so "flour" covers "plain flour" but "egg"
+// doesn't cover "eggplant". Makeable recipes rank by missing optional
+// ingredients, the rest by missing required ones; ties keep listing order.
+fn makeable_recipes(store: &dyn RecipeStore, pantry: &[String]) -> Result<MakeableRecipes, RecipeError> {
+    let pantry: Vec<String> = pantry
+        .iter()
+        .map(|item| format!(" {} ", plural::normalize(item)))
+        .filter(|item| !item.trim().is_empty())
+        .collect();
+    let covered = |name: &str| {
+        let name = format!(" {} ", plural::normalize(name));
+        pantry.iter().any(|item| name.contains(item.as_str()))
+    };
+
+    let mut makeable = Vec::new();
+    let mut almost = Vec::new();
+    for recipe in load_all_recipes(store)? {
+        let (mut missing, mut missing_optional) = (Vec::new(), Vec::new());
+        for ingredient in recipe.ingredients.iter().filter(|ingredient| !covered(&ingredient.name)) {
+            if ingredient.optional {
+                missing_optional.push(ingredient.name.clone());
+            } else {
+                missing.push(ingredient.name.clone());
+            }
+        }
+
+        let entry = MakeableRecipe { recipe: RecipeView::from(recipe), missing, missing_optional };
+        match entry.missing.len() {
+            0 => makeable.push(entry),
+            n if n <= MAX_ALMOST_MISSING => almost.push(entry),
+            _ => {}
+        }
+    }
+
+    makeable.sort_by_key(|entry| entry.missing_optional.len());
+    almost.sort_by_key(|entry| (entry.missing.len(), entry.missing_optional.len()));
+
+    Ok(MakeableRecipes { makeable, almost })
+}
+
+// Substring match on ingredient names, ignoring case and plurals. This scans
+// every recipe; an inverted index would be the next step if that gets slow.
+fn recipes_with_ingredient(store: &dyn RecipeStore, name: &str) -> Result<Vec<IngredientMatch>, RecipeError> {
+    let needle = plural::normalize(name);
+
+    let matches = load_all_recipes(store)?
+        .into_iter()
+        .filter_map(|recipe| {
+            let matched_ingredients: Vec<IngredientJson> = recipe
+                .ingredients
+                .iter()
+                .filter(|ingredient| plural::mentions(&ingredient.name, &needle))
+                .cloned()
+                .collect();
+
+            if matched_ingredients.is_empty() {
+                None
+            } else {
+                Some(IngredientMatch { matched_ingredients, recipe: recipe.into() })
+            }
+        })
+        .collect();
+
+    Ok(matches)
+}
+
+// Other recipes sharing at least one tag with `target`, most shared tags
+// first, then most shared dietary_info, then by ID. Comparisons ignore case.
+fn related_recipes(store: &dyn RecipeStore, target: &RecipeJson, limit: usize) -> Result<Vec<RelatedRecipe>, RecipeError> {
+    let shared = |values: &[String], wanted: &[String]| {
+        wanted
+            .iter()
+            .filter(|w| values.iter().any(|v| v.eq_ignore_ascii_case(w)))
+            .count()
+    };
+
+    let mut related: Vec<RelatedRecipe> = load_all_recipes(store)?
+        .into_iter()
+        .filter(|recipe| recipe.id != target.id)
+        .filter_map(|recipe| {
+            let shared_tags = shared(&recipe.tags, &target.tags);
+            let shared_dietary_info = shared(&recipe.dietary_info, &target.dietary_info);
+            (shared_tags > 0).then(|| RelatedRecipe { shared_tags, shared_dietary_info, recipe: recipe.into() })
+        })
+        .collect();
+
+    related.sort_by(|a, b| {
+        b.shared_tags
+            .cmp(&a.shared_tags)
+            .then(b.shared_dietary_info.cmp(&a.shared_dietary_info))
+            .then_with(|| a.recipe.recipe.id.cmp(&b.recipe.recipe.id))
+    });
+    related.truncate(limit);
+
+    Ok(related)
+}
+
+// Returns None when the stored recipe has zero servings, since there is no
+// base to scale from.
+fn scale_recipe(recipe: &RecipeJson, servings: u16) -> Option<RecipeJson> {
+    if recipe.servings == 0 {
+        return None;
+    }
+
+    let factor = servings as f32 / recipe.servings as f32;
+    let mut scaled = recipe.clone();
+    for ingredient in &mut scaled.ingredients {
+        ingredient.amount = round_amount(ingredient.amount * factor);
+    }
+    scaled.servings = servings;
+
+    Some(scaled)
+}
+
+// Steps without a duration still appear but take no time
+fn build_timeline(recipe: &RecipeJson) -> Timeline {
+    let mut steps = recipe.instructions.clone();
+    steps.sort_by_key(|step| step.order);
+
+    let mut elapsed: u32 = 0;
+    let steps = steps
+        .into_iter()
+        .map(|step| {
+            let start_at_mins = elapsed;
+            elapsed = elapsed.saturating_add(step.duration_mins.unwrap_or(0));
+            TimelineStep { step, start_at_mins }
+        })
+        .collect();
+
+    Timeline { steps, total_duration_mins: elapsed }
+}
+
+// Sum calories over required ingredients; optional ones are left out
+fn estimate_nutrition(recipe: &RecipeJson) -> NutritionEstimate {
+    let mut total_calories = 0.0;
+    let mut unknown = Vec::new();
+
+    for ingredient in recipe.ingredients.iter().filter(|i| !i.optional) {
+        match ingredient.calories_per_unit {
+            Some(calories) => total_calories += ingredient.amount * calories,
+            None => unknown.push(ingredient.name.clone()),
+        }
+    }
+
+    let per_serving = (recipe.servings > 0).then(|| round_amount(total_calories / recipe.servings as f32));
+
+    NutritionEstimate {
+        total_calories: round_amount(total_calories),
+        per_serving,
+        unknown,
+    }
+}
+
+fn build_shopping_list(store: &dyn RecipeStore, recipe_ids: &[String]) -> Result<ShoppingList, RecipeError> {
+    let mut items: Vec<ShoppingListItem> = Vec::new();
+    let mut missing = Vec::new();
+
+    for id in recipe_ids {
+        let recipe = match store.get(id)? {
+            Some(recipe) => recipe,
+            None => {
+                missing.push(id.clone());
+                continue;
+            }
+        };
+
+        for ingredient in recipe.ingredients {
+            // Same ingredient in a different unit stays a separate line
+            let existing = items.iter_mut().find(|item| {
+                item.name.trim().eq_ignore_ascii_case(ingredient.name.trim())
+                    && item.unit.trim().eq_ignore_ascii_case(ingredient.unit.trim())
+            });
+
+            match existing {
+                Some(item) => {
+                    item.amount += ingredient.amount;
+                    item.optional &= ingredient.optional;
+                }
+                None => items.push(ShoppingListItem {
+                    name: ingredient.name,
+                    amount: ingredient.amount,
+                    unit: ingredient.unit,
+                    optional: ingredient.optional,
+                }),
+            }
+        }
+    }
+
+    for item in &mut items {
+        item.amount = round_amount(item.amount);
+    }
+
+    Ok(ShoppingList { items, missing })
+}
+
+// Rewrite metric ingredient amounts in imperial units; other units are left as-is
+fn convert_to_imperial(recipe: &mut RecipeJson) {
+    for ingredient in &mut recipe.ingredients {
+        if let Some((amount, unit)) = units::to_imperial(ingredient.amount, &ingredient.unit) {
+            ingredient.amount = amount;
+            ingredient.unit = unit.to_string();
+        }
+    }
+}
+
+fn round_amount(amount: f32) -> f32 {
+    (amount * 100.0).round() / 100.0
+}
+
+fn read_recipe(bucket: &Bucket, key: &str) -> Result<Option<RecipeJson>, RecipeError> {
+    let data = bucket.get(key).map_err(|e| RecipeError::Storage(format!("Failed to get recipe: {:?}", e)))?;
+
+    match data {
+        Some(bytes) => {
+            let recipe = serde_json::from_slice(&bytes)
+                .map_err(|e| RecipeError::Serialization(format!("Failed to deserialize: {:?}", e)))?;
+            Ok(Some(recipe))
+        }
+        None => Ok(None),
+    }
+}
+
+// Validate and store a new recipe without touching the ID index; creates
+// inside with_index_batch have their index entries held for one flush
+fn store_new_recipe(bucket: &Bucket, mut recipe: RecipeJson) -> Result<String, RecipeError> {
+    prepare_recipe(&mut recipe, None)?;
+
+    // Generate ID if not provided; an explicit one must not clobber an existing recipe
+    if recipe.id.is_empty() {
+        recipe.id = generate_recipe_id(bucket, &recipe.name)?;
+    } else {
+        ensure_id_free(bucket, &recipe.id)?;
+    }
+
+    // Set timestamps
+    let now = current_timestamp();
+    recipe.created_at = now;
+    recipe.updated_at = now;
+
+    // Store recipe
+    let key = format!("recipe:{}", recipe.id);
+    let data = serde_json::to_vec(&recipe).map_err(|e| RecipeError::Serialization(format!("Failed to serialize: {:?}", e)))?;
+    bucket.set(&key, &data).map_err(|e| RecipeError::Storage(format!("Failed to store recipe: {:?}", e)))?;
+
+    Ok(recipe.id)
+}
+
+// RecipeError::Conflict if a live recipe already has `id`
+fn ensure_id_free(kv: &dyn BucketOps, id: &str) -> Result<(), RecipeError> {
+    if kv.exists(&format!("recipe:{}", id))? {
+        return Err(RecipeError::Conflict);
+    }
+    Ok(())
+}
+
+// Checks shared by every write path, before anything is stored. `previous`
+// is the stored recipe being replaced, if there is one.
+fn prepare_recipe(recipe: &mut RecipeJson, previous: Option<&RecipeJson>) -> Result<(), RecipeError> {
+    let mut problems = validate(recipe, max_time_mins()).err().unwrap_or_default();
+    problems.extend(step_gap_problem(recipe, previous));
+    if !problems.is_empty() {
+        return Err(RecipeError::Validation(problems));
+    }
+    normalize_units(recipe);
+    recipe.tags = normalize_labels(&recipe.tags);
+    recipe.dietary_info = normalize_labels(&recipe.dietary_info);
+    Ok(())
+}
+
+// The recipe POST would store, for `?dry_run=true`. The ID stays empty unless
+// the client supplied one, since minting one would use up a sequence number;
+// a supplied one gets the same conflict check as a real create.
+fn preview_create(store: &dyn RecipeStore, mut recipe: RecipeJson) -> Result<RecipeJson, RecipeError> {
+    prepare_recipe(&mut recipe, None)?;
+    if !recipe.id.is_empty() && store.exists(&recipe.id)? {
+        return Err(RecipeError::Conflict);
+    }
+
+    let now = current_timestamp();
+    recipe.created_at = now;
+    recipe.updated_at = now;
+
+    Ok(recipe)
+}
+
+// The recipe PUT would store, following upsert_recipe's create-or-update split
+fn preview_upsert(store: &dyn RecipeStore, id: &str, mut recipe: RecipeJson) -> Result<RecipeJson, RecipeError> {
+    recipe.id = id.to_string();
+    if let Some(existing) = store.get(id)? {
+        prepare_recipe(&mut recipe, Some(&existing))?;
+        Ok(recipe)
+    } else {
+        preview_create(store, recipe)
+    }
+}
+
+// Mints an ID using the `id_strategy` config key. For timestamps, the atomic
+// sequence guarantees IDs minted in the same instant still differ. Slugs take
+// the first suffix not used by a live or soft-deleted recipe; keyvalue has no
+// compare-and-set, so two creates racing for one name can still collide.
+fn generate_recipe_id(bucket: &Bucket, name: &str) -> Result<String, RecipeError> {
+    match ids::IdStrategy::from_config(config_value("id_strategy").as_deref()) {
+        ids::IdStrategy::Timestamp => {
+            let sequence = increment(bucket, ID_SEQUENCE_KEY, 1)
+                .map_err(|e| RecipeError::Storage(format!("Failed to allocate ID sequence: {:?}", e)))?;
+            Ok(ids::timestamp_id(current_timestamp_nanos(), sequence))
+        }
+        ids::IdStrategy::Uuid => {
+            let (high, low) = (wasi::random::random::get_random_u64(), wasi::random::random::get_random_u64());
+            Ok(ids::uuid_v4(high, low))
+        }
+        ids::IdStrategy::Slug => {
+            let slug = ids::slugify(name);
+            let taken = |id: &str| -> Result<bool, RecipeError> {
+                let check = |key: String| bucket.exists(&key).map_err(|e| RecipeError::Storage(format!("Failed to check recipe: {:?}", e)));
+                Ok(check(format!("recipe:{}", id))? || check(format!("deleted:{}", id))?)
+            };
+
+            let mut attempt = 1;
+            loop {
+                let candidate = ids::slug_candidate(&slug, attempt);
+                if !taken(&candidate)? {
+                    return Ok(candidate);
+                }
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// Strict parse of a recipe body, except that the fields responses add are
+// dropped, so a fetched recipe can be sent straight back. Bodies without
+// them are parsed from the bytes directly and keep serde's error position.
+fn parse_recipe(body: &[u8]) -> Result<RecipeJson, serde_json::Error> {
+    serde_json::from_slice::<RecipeJson>(body).or_else(|strict| match serde_json::from_slice(body) {
+        Ok(value @ serde_json::Value::Object(_)) if has_response_fields(&value) => recipe_from_value(value),
+        _ => Err(strict),
+    })
+}
+
+// As parse_recipe, for a body that's already been parsed, e.g. a bulk item
+fn recipe_from_value(mut value: serde_json::Value) -> Result<RecipeJson, serde_json::Error> {
+    if let Some(fields) = value.as_object_mut() {
+        for field in RESPONSE_ONLY_FIELDS {
+            fields.remove(field);
+        }
+    }
+    serde_json::from_value(value)
+}
+
+fn has_response_fields(value: &serde_json::Value) -> bool {
+    RESPONSE_ONLY_FIELDS.iter().any(|field| value.get(field).is_some())
+}
+
+fn create_recipes_bulk(store: &dyn RecipeStore, items: Vec<serde_json::Value>) -> Result<Vec<BulkResult>, RecipeError> {
+    let mut results = Vec::with_capacity(items.len());
+
+    for (index, item) in items.into_iter().enumerate() {
+        let recipe = match recipe_from_value(item) {
+            Ok(recipe) => recipe,
+            Err(e) => {
+                results.push(BulkResult::failed(index, 400, None, e.to_string()));
+                continue;
+            }
+        };
+
+        let requested_id = (!recipe.id.is_empty()).then(|| recipe.id.clone());
+        let warnings = unit_warnings(&recipe);
+
+        match store.create(recipe) {
+            Ok(id) => results.push(BulkResult { warnings, ..BulkResult::succeeded(index, 201, id) }),
+            Err(e) => results.push(BulkResult::from_error(index, requested_id, e)),
+        }
+    }
+
+    Ok(results)
+}
+
+// Existing IDs are skipped or overwritten depending on `mode`; everything
+// else is created. Existence is checked per item, so an ID repeated within
+// the file is created once and then skipped or updated. Created items get
+// 201, and updated or skipped ones 200.
+fn import_recipes(store: &dyn RecipeStore, items: Vec<serde_json::Value>, mode: ImportMode) -> Result<Vec<BulkResult>, RecipeError> {
+    let mut results = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        results.push(import_item(store, index, serde_json::from_value(item), &mode)?);
+    }
+    Ok(results)
+}
+
+// Import one already-parsed item: 201 when created, 200 when it existed and
+// was skipped or replaced
+fn import_item(
+    store: &dyn RecipeStore,
+    index: usize,
+    parsed: Result<RecipeJson, serde_json::Error>,
+    mode: &ImportMode,
+) -> Result<BulkResult, RecipeError> {
+    let recipe = match parsed {
+        Ok(recipe) => recipe,
+        Err(e) => return Ok(BulkResult::failed(index, 400, None, e.to_string())),
+    };
+
+    let requested_id = (!recipe.id.is_empty()).then(|| recipe.id.clone());
+    let exists = match &requested_id {
+        Some(id) => store.get(id)?.is_some(),
+        None => false,
+    };
+
+    let outcome = match (exists, mode) {
+        (true, ImportMode::Merge) => Ok((200, recipe.id)),
+        (true, ImportMode::Replace) => {
+            let id = recipe.id.clone();
+            store.update(&id, recipe).map(|_| (200, id))
+        }
+        (false, _) => store.create(recipe).map(|id| (201, id)),
+    };
+
+    Ok(match outcome {
+        Ok((status, id)) => BulkResult::succeeded(index, status, id),
+        Err(e) => BulkResult::from_error(index, requested_id, e),
+    })
+}
+
+// Streaming import of newline-delimited JSON, one recipe per line. Each line
+// is parsed and stored before the next is read, and only failures are kept
+// for the response, so memory doesn't grow with the size of the upload.
+fn import_recipes_ndjson(store: &dyn RecipeStore, request: IncomingRequest, mode: ImportMode) -> Result<NdjsonImportSummary, RecipeError> {
+    // A body that can't be read is the client's problem, as for other imports
+    let mut lines = LineReader::new(request).map_err(|e| RecipeError::Validation(vec![e]))?;
+    let mut summary = NdjsonImportSummary::default();
+    let mut index = 0;
+
+    while let Some(line) = lines.next_line() {
+        let parsed = match line {
+            Line::Text(text) if text.iter().all(u8::is_ascii_whitespace) => continue,
+            Line::Text(text) => serde_json::from_slice::<RecipeJson>(&text),
+            Line::TooLong => {
+                summary.record(BulkResult::failed(index, 413, None, format!("line is longer than {} bytes", MAX_NDJSON_LINE_BYTES)), &mode);
+                index += 1;
+                continue;
+            }
+        };
+
+        summary.record(import_item(store, index, parsed, &mode)?, &mode);
+        index += 1;
+        if index % NDJSON_INDEX_FLUSH_EVERY == 0 {
+            flush_index_batch()?;
+        }
+    }
+
+    if let Some(e) = lines.error {
+        return Err(RecipeError::Validation(vec![e]));
+    }
+    Ok(summary)
+}
+
+// PUT semantics: store the recipe under a client-chosen ID, creating it if
+// it doesn't exist yet. Returns the stored recipe and whether
+// it was created.
+fn upsert_recipe(store: &dyn RecipeStore, id: &str, mut recipe: RecipeJson) -> Result<(RecipeJson, bool), RecipeError> {
+    if store.get(id)?.is_some() {
+        return Ok((store.update(id, recipe)?, false));
+    }
+
+    recipe.id = id.to_string();
+    store.create(recipe)?;
+
+    let stored = store.get(id)?.ok_or_else(|| RecipeError::Storage(format!("Recipe {} missing after create", id)))?;
+    Ok((stored, true))
+}
+
+// Store the outgoing value as the next version and drop the one that falls
+// out of the retention window
+fn snapshot_version(bucket: &Bucket, id: &str, previous: &[u8]) -> Result<(), RecipeError> {
+    let version = increment(bucket, &version_counter_key(id), 1)
+        .map_err(|e| RecipeError::Storage(format!("Failed to allocate version: {:?}", e)))?;
+
+    bucket
+        .set(&version_key(id, version), previous)
+        .map_err(|e| RecipeError::Storage(format!("Failed to store version: {:?}", e)))?;
+
+    if version > MAX_RETAINED_VERSIONS {
+        bucket
+            .delete(&version_key(id, version - MAX_RETAINED_VERSIONS))
+            .map_err(|e| RecipeError::Storage(format!("Failed to prune version: {:?}", e)))?;
+    }
+
+    Ok(())
+}
+
+// Retained versions, newest first
+fn list_versions(bucket: &Bucket, id: &str) -> Result<Vec<VersionSummary>, RecipeError> {
+    let mut versions = Vec::new();
+    for version in retained_versions(bucket, id)?.rev() {
+        if let Some(recipe) = read_recipe(bucket, &version_key(id, version))? {
+            versions.push(VersionSummary { version, name: recipe.name, updated_at: recipe.updated_at });
+        }
+    }
+
+    Ok(versions)
+}
+
+fn retained_versions(bucket: &Bucket, id: &str) -> Result<std::ops::RangeInclusive<u64>, RecipeError> {
+    // Incrementing by zero reads the counter atomically
+    let latest = increment(bucket, &version_counter_key(id), 0)
+        .map_err(|e| RecipeError::Storage(format!("Failed to read version counter: {:?}", e)))?;
+
+    Ok(latest.saturating_sub(MAX_RETAINED_VERSIONS - 1).max(1)..=latest)
+}
+
+fn views_key(id: &str) -> String {
+    format!("views:{}", id)
+}
+
+// Count a fetch of the recipe. A failure is only logged; the read itself has
+// already succeeded.
+fn record_view(store: &dyn RecipeStore, id: &str) {
+    if let Err(e) = store.record_view(id) {
+        log(Level::Warn, "recipe-api", &format!("Failed to record view of {}: {}", id, e));
+    }
+}
+
+// The most-viewed live recipes, most views first and by ID among equals.
+// Recipes that have never been fetched aren't included.
+fn popular_recipes(store: &dyn RecipeStore, limit: usize) -> Result<Vec<PopularRecipe>, RecipeError> {
+    let mut counts = Vec::new();
+    for id in store.list()? {
+        let views = store.views(&id)?;
+        if views > 0 {
+            counts.push((views, id));
+        }
+    }
+    counts.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    counts.truncate(limit);
+
+    let mut popular = Vec::with_capacity(counts.len());
+    for (views, id) in counts {
+        if let Some(recipe) = store.get(&id)? {
+            popular.push(PopularRecipe { views, recipe: RecipeView::from(recipe) });
+        }
+    }
+
+    Ok(popular)
+}
+
+fn rating_sum_key(id: &str) -> String {
+    format!("rating:{}:sum", id)
+}
+
+fn rating_count_key(id: &str) -> String {
+    format!("rating:{}:count", id)
+}
+
+fn rated_at_key(id: &str) -> String {
+    format!("rating:{}:at", id)
+}
+
+// RecipeError::NotFound if there's no live recipe with `id`
+fn rate_recipe(store: &dyn RecipeStore, id: &str, rating: u64) -> Result<RatingSummary, RecipeError> {
+    if !store.exists(id)? {
+        return Err(RecipeError::NotFound);
+    }
+
+    store.rate(id, rating)
+}
+
+// Add one rating to the recipe's running totals. Each total is bumped with an
+// atomic increment, so concurrent ratings are never lost; a read landing
+// between the two may briefly see the count without the new sum. The time
+// is a plain overwrite, since concurrent ratings land within the same second
+// or two anyway.
+fn add_rating(bucket: &Bucket, id: &str, rating: u64) -> Result<RatingSummary, RecipeError> {
+    let count = increment(bucket, &rating_count_key(id), 1)
+        .map_err(|e| RecipeError::Storage(format!("Failed to count rating: {:?}", e)))?;
+    let sum = increment(bucket, &rating_sum_key(id), rating)
+        .map_err(|e| RecipeError::Storage(format!("Failed to add rating: {:?}", e)))?;
+
+    let rated_at = current_timestamp();
+    bucket
+        .set(&rated_at_key(id), rated_at.to_string().as_bytes())
+        .map_err(|e| RecipeError::Storage(format!("Failed to store rating time: {:?}", e)))?;
+
+    Ok(RatingSummary::from_totals(sum, count, rated_at))
+}
+
+fn load_rating(bucket: &Bucket, id: &str) -> Result<RatingSummary, RecipeError> {
+    // Checked first so reading doesn't create counters for unrated recipes
+    let rated = bucket
+        .exists(&rating_count_key(id))
+        .map_err(|e| RecipeError::Storage(format!("Failed to check rating: {:?}", e)))?;
+    if !rated {
+        return Ok(RatingSummary::from_totals(0, 0, 0));
+    }
+
+    let count = increment(bucket, &rating_count_key(id), 0)
+        .map_err(|e| RecipeError::Storage(format!("Failed to read rating count: {:?}", e)))?;
+    let sum = increment(bucket, &rating_sum_key(id), 0)
+        .map_err(|e| RecipeError::Storage(format!("Failed to read rating sum: {:?}", e)))?;
+    // Totals from before rating times were kept have none; 0 defers to updated_at
+    let rated_at = bucket
+        .get(&rated_at_key(id))
+        .map_err(|e| RecipeError::Storage(format!("Failed to read rating time: {:?}", e)))?
+        .and_then(|data| String::from_utf8(data).ok())
+        .and_then(|time| time.parse().ok())
+        .unwrap_or(0);
+
+    Ok(RatingSummary::from_totals(sum, count, rated_at))
+}
+
+// Rating totals for serving a recipe. A lookup failure is logged and leaves
+// them out rather than failing the request.
+fn served_rating(store: &dyn RecipeStore, id: &str) -> Option<RatingSummary> {
+    store.rating(id)
+        .inspect_err(|e| log(Level::Warn, "recipe-api", &format!("Failed to load rating of {}: {}", id, e)))
+        .ok()
+}
+
+// Ratings are stored apart from the recipe, so a new one doesn't touch
+// `updated_at`. Last-Modified takes whichever changed last.
+fn last_modified_at(recipe: &RecipeJson, rating: Option<&RatingSummary>) -> u64 {
+    rating.map_or(recipe.updated_at, |rating| rating.rated_at.max(recipe.updated_at))
+}
+
+fn version_key(id: &str, version: u64) -> String {
+    format!("recipe:{}:v{}", id, version)
+}
+
+fn version_counter_key(id: &str) -> String {
+    format!("recipe:{}:versions", id)
+}
+
+// Rewrite known unit aliases to their canonical form; unknown units are kept as-is
+fn normalize_units(recipe: &mut RecipeJson) {
+    for ingredient in &mut recipe.ingredients {
+        if let Some(unit) = units::canonical(&ingredient.unit) {
+            ingredient.unit = unit.to_string();
+        }
+    }
+}
+
+// One warning per ingredient whose unit isn't in the known set
+fn unit_warnings(recipe: &RecipeJson) -> Vec<String> {
+    recipe
+        .ingredients
+        .iter()
+        .filter(|ingredient| units::canonical(&ingredient.unit).is_none())
+        .map(|ingredient| format!("ingredient '{}' has unrecognized unit '{}'", ingredient.name, ingredient.unit))
+        .collect()
+}
+
+// `max_time_mins` bounds prep and cook time; callers pass max_time_mins()
+fn validate(recipe: &RecipeJson, max_time_mins: u64) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+
+    // Empty means "generate one", which is always valid
+    if !recipe.id.is_empty() {
+        if recipe.id.len() > MAX_IDENTIFIER_LEN {
+            problems.push(format!("id must be at most {} characters", MAX_IDENTIFIER_LEN));
+        } else if let Some(problem) = ids::explicit_id_problem(&recipe.id) {
+            problems.push(problem);
+        }
+    }
+
+    if recipe.name.trim().is_empty() {
+        problems.push("name must not be empty".to_string());
+    }
+
+    if recipe.servings < 1 {
+        problems.push("servings must be at least 1".to_string());
+    }
+
+    if !DIFFICULTY_LEVELS.contains(&recipe.difficulty.as_str()) {
+        problems.push(format!(
+            "difficulty must be one of: {} (got '{}')",
+            DIFFICULTY_LEVELS.join(", "),
+            recipe.difficulty
+        ));
+    }
+
+    // Zero is fine, e.g. for a no-cook recipe; anything past the bound is
+    // most likely a typo
+    for (field, minutes) in [("prep_time_mins", recipe.prep_time_mins), ("cook_time_mins", recipe.cook_time_mins)] {
+        if u64::from(minutes) > max_time_mins {
+            problems.push(format!("{} must be at most {} (got {})", field, max_time_mins, minutes));
+        }
+    }
+
+    for ingredient in &recipe.ingredients {
+        if ingredient.amount < 0.0 {
+            problems.push(format!("ingredient '{}' has a negative amount", ingredient.name));
+        }
+    }
+
+    let mut seen_orders = Vec::new();
+    for step in &recipe.instructions {
+        if seen_orders.contains(&step.order) {
+            problems.push(format!("step order {} is used more than once", step.order));
+        } else {
+            seen_orders.push(step.order);
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+// Everything that makes a stored recipe unusable: validate()'s rules plus
+// gaps that writes allow but a cook can't work from. Read-only, for
+// `?incomplete=true`.
+fn lint_recipe(recipe: &RecipeJson) -> Vec<String> {
+    let mut problems = validate(recipe, max_time_mins()).err().unwrap_or_default();
+
+    if recipe.ingredients.is_empty() {
+        problems.push("recipe has no ingredients".to_string());
+    }
+    if recipe.instructions.is_empty() {
+        problems.push("recipe has no steps".to_string());
+    }
+
+    problems
+}
+
+// Steps written by a client must be numbered 1..=n, unless `?allow_gaps=true`.
+// This lives outside validate() so recipes stored before the rule can still
+// be edited in ways that leave their steps alone: a write keeping the step
+// orders of the recipe it replaces is exempt.
+fn step_gap_problem(recipe: &RecipeJson, previous: Option<&RecipeJson>) -> Option<String> {
+    let allow_gaps = REQUEST_CONTEXT.with(|ctx| ctx.borrow().allow_step_gaps);
+    if allow_gaps || previous.is_some_and(|previous| step_orders(previous) == step_orders(recipe)) {
+        return None;
+    }
+
+    let count = recipe.instructions.len();
+    let missing: Vec<String> = (1..=count)
+        .filter(|&order| !recipe.instructions.iter().any(|step| step.order as usize == order))
+        .map(|order| order.to_string())
+        .collect();
+
+    if missing.is_empty() {
+        return None;
+    }
+
+    // Duplicates are already reported by validate()
+    Some(format!(
+        "step orders must run from 1 to {} without gaps; missing {}",
+        count,
+        missing.join(", ")
+    ))
+}
+
+fn step_orders(recipe: &RecipeJson) -> Vec<u8> {
+    let mut orders: Vec<u8> = recipe.instructions.iter().map(|step| step.order).collect();
+    orders.sort_unstable();
+    orders
+}
+
+// Load a recipe, let `edit` change it, then bump `updated_at` and store it.
+// `edit` returns false to leave the stored recipe untouched. A missing
+// recipe is RecipeError::NotFound.
+fn edit_recipe<F>(store: &dyn RecipeStore, id: &str, edit: F) -> Result<RecipeJson, RecipeError>
+where
+    F: FnOnce(&mut RecipeJson) -> bool,
+{
+    let mut recipe = store.get(id)?.ok_or(RecipeError::NotFound)?;
+
+    if edit(&mut recipe) {
+        recipe.updated_at = current_timestamp();
+        recipe = store.update(id, recipe)?;
+    }
+
+    Ok(recipe)
+}
+
+// Renumber steps 1..=n in the sequence given by `order`, which must name
+// every existing step's `order` exactly once. Steps are untouched on error.
+// Older recipes can hold two steps with one `order`; those can't be told
+// apart by number, so they're refused rather than one being dropped.
+fn reorder_steps(steps: &mut Vec<StepJson>, order: &[u8]) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+
+    let mut stored: Vec<u8> = steps.iter().map(|step| step.order).collect();
+    stored.sort_unstable();
+    let mut shared: Vec<u8> = stored.windows(2).filter(|pair| pair[0] == pair[1]).map(|pair| pair[0]).collect();
+    shared.dedup();
+    for order in shared {
+        problems.push(format!("more than one step is numbered {}; renumber them before reordering", order));
+    }
+
+    for (position, wanted) in order.iter().enumerate() {
+        if order[..position].contains(wanted) {
+            problems.push(format!("step {} is listed more than once", wanted));
+        } else if !steps.iter().any(|step| step.order == *wanted) {
+            problems.push(format!("step {} does not exist", wanted));
+        }
+    }
+    for step in steps.iter() {
+        if !order.contains(&step.order) {
+            problems.push(format!("step {} is missing from order", step.order));
+        }
+    }
+    if problems.is_empty() && order.len() != steps.len() {
+        problems.push(format!("order lists {} steps but the recipe has {}", order.len(), steps.len()));
+    }
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    let mut remaining = std::mem::take(steps);
+    for (position, wanted) in order.iter().enumerate() {
+        if let Some(index) = remaining.iter().position(|step| step.order == *wanted) {
+            let mut step = remaining.remove(index);
+            step.order = (position + 1) as u8;
+            steps.push(step);
+        }
+    }
+
+    Ok(())
+}
+
+// "Quick  Dinner", "quick dinner" and "quick-dinner" all become "quick-dinner":
+// lowercase words joined by single dashes. Underscores count as separators.
+fn kebab_case(label: &str) -> String {
+    label
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Tags or dietary info in kebab-case, without blanks or duplicates, in
+// first-seen order
+fn normalize_labels(labels: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for label in labels.iter().map(|label| kebab_case(label)) {
+        if !label.is_empty() && !normalized.contains(&label) {
+            normalized.push(label);
+        }
+    }
+    normalized
+}
+
+// Combine tag lists, dropping blanks and case-insensitive duplicates while
+// keeping the first-seen casing
+fn merge_tags(existing: &[String], additions: &[String]) -> Vec<String> {
+    let mut merged: Vec<String> = Vec::new();
+    for tag in existing.iter().chain(additions) {
+        let tag = tag.trim();
+        if !tag.is_empty() && !merged.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            merged.push(tag.to_string());
+        }
+    }
+    merged
+}
+
+// Apply a field merge or JSON Patch to an existing recipe. The id and
+// creation time are immutable; everything else is re-checked by deserializing
+// the patched document back into a RecipeJson.
+fn apply_patch(existing: &RecipeJson, patch: RecipePatch) -> Result<RecipeJson, json_patch::PatchError> {
+    let invalid = json_patch::PatchError::Invalid;
+
+    let original = serde_json::to_value(existing).map_err(|e| invalid(format!("Failed to serialize: {:?}", e)))?;
+    let mut patched = original.clone();
+
+    match patch {
+        RecipePatch::Merge(fields) => {
+            if let Some(merged) = patched.as_object_mut() {
+                for (key, value) in fields {
+                    merged.insert(key, value);
+                }
+            }
+        }
+        RecipePatch::Operations(operations) => json_patch::apply(&mut patched, &operations)?,
+    }
+
+    for field in ["id", "created_at"] {
+        if patched.get(field) != original.get(field) {
+            return Err(invalid(format!("Field '{}' cannot be changed", field)));
+        }
+    }
+
+    let mut recipe: RecipeJson =
+        serde_json::from_value(patched).map_err(|e| invalid(format!("Invalid patch: {}", e)))?;
+    recipe.updated_at = current_timestamp();
+
+    Ok(recipe)
+}
+
+// Delete each ID that names a live recipe (204); the rest are 404. An ID
+// repeated in the batch reports 204 again rather than 404. The slot index
+// has no shared list to rewrite, so each removal only touches that ID's own
+// index keys.
+fn delete_recipes_batch(store: &dyn RecipeStore, ids: &[String], hard: bool) -> Result<Vec<BulkResult>, RecipeError> {
+    let mut results = Vec::with_capacity(ids.len());
+    let mut deleted: Vec<&String> = Vec::new();
+    for (index, id) in ids.iter().enumerate() {
+        if deleted.contains(&id) {
+            results.push(BulkResult::succeeded(index, 204, id.clone()));
+            continue;
+        }
+
+        if store.exists(id)? {
+            store.delete(id, hard)?;
+            deleted.push(id);
+            results.push(BulkResult::succeeded(index, 204, id.clone()));
+        } else {
+            results.push(BulkResult::failed(index, 404, Some(id.clone()), "recipe not found".to_string()));
+        }
+    }
+
+    Ok(results)
+}
+
+fn remove_recipe(bucket: &Bucket, id: &str, hard: bool) -> Result<(), RecipeError> {
+    let key = format!("recipe:{}", id);
+    let deleted_key = format!("deleted:{}", id);
+
+    if hard {
+        bucket.delete(&deleted_key).map_err(|e| RecipeError::Storage(format!("Failed to delete recipe: {:?}", e)))?;
+        remove_from_index(bucket, DELETED_INDEX, id)?;
+
+        for version in retained_versions(bucket, id)? {
+            bucket
+                .delete(&version_key(id, version))
+                .map_err(|e| RecipeError::Storage(format!("Failed to delete version: {:?}", e)))?;
+        }
+        bucket
+            .delete(&version_counter_key(id))
+            .map_err(|e| RecipeError::Storage(format!("Failed to delete version counter: {:?}", e)))?;
+        for key in [views_key(id), rating_count_key(id), rating_sum_key(id), rated_at_key(id)] {
+            bucket
+                .delete(&key)
+                .map_err(|e| RecipeError::Storage(format!("Failed to delete counter: {:?}", e)))?;
+        }
+
+        // The image is in another store; failing to reach it shouldn't block the delete
+        if let Err(e) = delete_image(bucket, id) {
+            log(Level::Warn, "recipe-api", &format!("Failed to delete image for {}: {}", id, e));
+        }
+    } else if let Some(data) = bucket.get(&key).map_err(|e| RecipeError::Storage(format!("Failed to get recipe: {:?}", e)))? {
+        bucket.set(&deleted_key, &data).map_err(|e| RecipeError::Storage(format!("Failed to store deleted recipe: {:?}", e)))?;
+        add_to_index(bucket, DELETED_INDEX, id)?;
+    }
+
+    bucket.delete(&key).map_err(|e| RecipeError::Storage(format!("Failed to delete recipe: {:?}", e)))?;
+
+    // Remove from recipe IDs list and favorites
+    if !key_listing_available(bucket) {
+        remove_from_index(bucket, RECIPE_INDEX, id)?;
+    }
+    remove_from_index(bucket, FAVORITES_INDEX, id)?;
+
+    Ok(())
+}
+
+enum RestoreOutcome {
+    Restored(Box<RecipeJson>),
+    NotDeleted,
+    // A live recipe has taken the ID since it was deleted
+    Conflict,
+}
+
+fn restore_recipe(bucket: &Bucket, id: &str) -> Result<RestoreOutcome, RecipeError> {
+    let key = format!("recipe:{}", id);
+    let deleted_key = format!("deleted:{}", id);
+
+    let recipe = match read_recipe(bucket, &deleted_key)? {
+        Some(recipe) => recipe,
+        None => return Ok(RestoreOutcome::NotDeleted),
+    };
+    if bucket.exists(&key).map_err(|e| RecipeError::Storage(format!("Failed to check recipe: {:?}", e)))? {
+        return Ok(RestoreOutcome::Conflict);
+    }
+
+    let data = serde_json::to_vec(&recipe).map_err(|e| RecipeError::Serialization(format!("Failed to serialize: {:?}", e)))?;
+    bucket.set(&key, &data).map_err(|e| RecipeError::Storage(format!("Failed to restore recipe: {:?}", e)))?;
+    if !key_listing_available(bucket) {
+        add_to_index(bucket, RECIPE_INDEX, id)?;
+    }
+
+    bucket.delete(&deleted_key).map_err(|e| RecipeError::Storage(format!("Failed to delete recipe: {:?}", e)))?;
+    remove_from_index(bucket, DELETED_INDEX, id)?;
+
+    Ok(RestoreOutcome::Restored(Box::new(recipe)))
+}
+
+fn load_deleted_recipes(bucket: &Bucket) -> Result<Vec<RecipeJson>, RecipeError> {
+    let ids = load_index(bucket, DELETED_INDEX)?;
+
+    let mut recipes = Vec::with_capacity(ids.len());
+    for id in ids {
+        recipes.extend(read_recipe(bucket, &format!("deleted:{}", id))?);
+    }
+
+    Ok(recipes)
+}
+
+fn put_recipe_image(store: &dyn RecipeStore, id: &str, request: IncomingRequest, response_out: ResponseOutparam) {
+    let Some(content_type) = media_type(&request.headers()).filter(|t| IMAGE_CONTENT_TYPES.contains(&t.as_str())) else {
+        send_response(415, b"Unsupported Media Type: expected image/jpeg, image/png or image/webp", response_out);
+        return;
+    };
+
+    match store.get(id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            send_response(404, b"Recipe not found", response_out);
+            return;
+        }
+        Err(e) => {
+            send_error("Error getting recipe", e, response_out);
+            return;
+        }
+    }
+
+    let body = match read_request_body_capped(request, MAX_IMAGE_BYTES) {
+        Ok(Some(body)) if !body.is_empty() => body,
+        Ok(Some(_)) => {
+            send_response(400, b"Image body is empty", response_out);
+            return;
+        }
+        Ok(None) => {
+            let message = format!("Image exceeds {} bytes", MAX_IMAGE_BYTES);
+            send_response(413, message.as_bytes(), response_out);
+            return;
+        }
+        Err(_) => {
+            send_response(400, b"Failed to read body", response_out);
+            return;
+        }
+    };
+
+    match store_image(id, &content_type, &body) {
+        Ok(()) => send_response(204, b"", response_out),
+        Err(e) => {
+            send_error("Error storing image", e, response_out);
+        }
+    }
+}
+
+// Blobstore keeps no content type, so it sits in keyvalue beside the recipe
+fn image_type_key(id: &str) -> String {
+    format!("image:{}", id)
+}
+
+fn image_container() -> Result<Container, RecipeError> {
+    let exists = container_exists(IMAGE_CONTAINER).map_err(|e| RecipeError::Storage(format!("Failed to check image container: {}", e)))?;
+    let container = if exists { get_container(IMAGE_CONTAINER) } else { create_container(IMAGE_CONTAINER) };
+    container.map_err(|e| RecipeError::Storage(format!("Failed to open image container: {}", e)))
+}
+
+// Replaces any image already stored for the recipe
+fn store_image(id: &str, content_type: &str, data: &[u8]) -> Result<(), RecipeError> {
+    let container = image_container()?;
+
+    let value = OutgoingValue::new_outgoing_value();
+    container.write_data(id, &value).map_err(|e| RecipeError::Storage(format!("Failed to write image: {}", e)))?;
+    {
+        let stream = value
+            .outgoing_value_write_body()
+            .map_err(|_| RecipeError::Storage("Failed to open image stream".to_string()))?;
+        write_all(&stream, data).map_err(|e| RecipeError::Storage(format!("Failed to write image: {:?}", e)))?;
+    }
+    OutgoingValue::finish(value).map_err(|e| RecipeError::Storage(format!("Failed to finish image: {}", e)))?;
+
+    let bucket = open_bucket()?;
+    bucket
+        .set(&image_type_key(id), content_type.as_bytes())
+        .map_err(|e| RecipeError::Storage(format!("Failed to store image type: {:?}", e)))
+}
+
+struct StoredImage {
+    content_type: String,
+    size: u64,
+    body: InputStream,
+}
+
+fn load_image(id: &str) -> Result<Option<StoredImage>, RecipeError> {
+    let bucket = open_bucket()?;
+    let Some(content_type) = bucket
+        .get(&image_type_key(id))
+        .map_err(|e| RecipeError::Storage(format!("Failed to get image type: {:?}", e)))?
+    else {
+        return Ok(None);
+    };
+
+    let container = image_container()?;
+    if !container.has_object(id).map_err(|e| RecipeError::Storage(format!("Failed to check image: {}", e)))? {
+        return Ok(None);
+    }
+
+    let size = container.object_info(id).map_err(|e| RecipeError::Storage(format!("Failed to get image info: {}", e)))?.size;
+    // Uploads are never empty, so an empty object is a broken write, and
+    // there'd be no byte range to ask for: get-data's end offset is inclusive
+    if size == 0 {
+        return Ok(None);
+    }
+    let value = container.get_data(id, 0, size - 1).map_err(|e| RecipeError::Storage(format!("Failed to get image: {}", e)))?;
+    let body = IncomingValue::incoming_value_consume_async(value).map_err(|e| RecipeError::Storage(format!("Failed to read image: {}", e)))?;
+
+    Ok(Some(StoredImage {
+        content_type: String::from_utf8_lossy(&content_type).into_owned(),
+        size,
+        body,
+    }))
+}
+
+fn delete_image(bucket: &Bucket, id: &str) -> Result<(), RecipeError> {
+    bucket
+        .delete(&image_type_key(id))
+        .map_err(|e| RecipeError::Storage(format!("Failed to delete image type: {:?}", e)))?;
+
+    let container = image_container()?;
+    if container.has_object(id).map_err(|e| RecipeError::Storage(format!("Failed to check image: {}", e)))? {
+        container.delete_object(id).map_err(|e| RecipeError::Storage(format!("Failed to delete image: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+// wasi:keyvalue has no TTL, so records carry their own timestamp and count
+// as absent once older than IDEMPOTENCY_TTL_SECS. The next create under the
+// same key overwrites them. Two concurrent first uses of a key can both
+// create, since the store has no compare-and-swap to claim the key.
+fn find_idempotent_create(key: &str) -> Result<Option<IdempotencyRecord>, RecipeError> {
+    let bucket = open_bucket()?;
+
+    let Some(data) = bucket
+        .get(&format!("idempotency:{}", key))
+        .map_err(|e| RecipeError::Storage(format!("Failed to get idempotency record: {:?}", e)))?
+    else {
+        return Ok(None);
+    };
+
+    let record: IdempotencyRecord =
+        serde_json::from_slice(&data).map_err(|e| RecipeError::Serialization(format!("Failed to deserialize: {:?}", e)))?;
+    let expired = current_timestamp().saturating_sub(record.stored_at) > IDEMPOTENCY_TTL_SECS;
+
+    Ok((!expired).then_some(record))
+}
+
+fn record_idempotent_create(key: &str, recipe_id: &str, warnings: &[String]) -> Result<(), RecipeError> {
+    let bucket = open_bucket()?;
+
+    let record = IdempotencyRecord {
+        recipe_id: recipe_id.to_string(),
+        warnings: warnings.to_vec(),
+        stored_at: current_timestamp(),
+    };
+    let data = serde_json::to_vec(&record).map_err(|e| RecipeError::Serialization(format!("Failed to serialize: {:?}", e)))?;
+
+    bucket
+        .set(&format!("idempotency:{}", key), &data)
+        .map_err(|e| RecipeError::Storage(format!("Failed to store idempotency record: {:?}", e)))
+}
+
+// RecipeError::NotFound if there's no live recipe with `id`. Checks the
+// record itself, since the index can lag a write or trail a delete.
+fn set_favorite(bucket: &Bucket, id: &str, favorite: bool) -> Result<(), RecipeError> {
+    let exists = bucket
+        .exists(&format!("recipe:{}", id))
+        .map_err(|e| RecipeError::Storage(format!("Failed to check recipe: {:?}", e)))?;
+    if !exists {
+        return Err(RecipeError::NotFound);
+    }
+
+    if favorite {
+        add_to_index(bucket, FAVORITES_INDEX, id)?;
+    } else {
+        remove_from_index(bucket, FAVORITES_INDEX, id)?;
+    }
+
+    Ok(())
+}
+
+fn collection_key(id: &str) -> String {
+    format!("collection:{}", id)
+}
+
+fn load_collection(id: &str) -> Result<Option<CollectionJson>, RecipeError> {
+    let bucket = open_bucket()?;
+    read_collection(&bucket, id)
+}
+
+fn read_collection(bucket: &Bucket, id: &str) -> Result<Option<CollectionJson>, RecipeError> {
+    match bucket
+        .get(&collection_key(id))
+        .map_err(|e| RecipeError::Storage(format!("Failed to get collection: {:?}", e)))?
+    {
+        Some(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| RecipeError::Serialization(format!("Failed to deserialize collection: {:?}", e))),
+        None => Ok(None),
+    }
+}
+
+fn write_collection(bucket: &Bucket, collection: &CollectionJson) -> Result<(), RecipeError> {
+    let data = serde_json::to_vec(collection)
+        .map_err(|e| RecipeError::Serialization(format!("Failed to serialize collection: {:?}", e)))?;
+    bucket
+        .set(&collection_key(&collection.id), &data)
+        .map_err(|e| RecipeError::Storage(format!("Failed to store collection: {:?}", e)))
+}
+
+// Collections always get UUIDs; `id_strategy` only applies to recipes.
+// Every listed recipe must exist, and duplicates are dropped.
+fn create_collection(store: &dyn RecipeStore, request: CreateCollectionRequest) -> Result<String, RecipeError> {
+    let name = request.name.trim().to_string();
+    if name.is_empty() {
+        return Err(RecipeError::Validation(vec!["name must not be empty".to_string()]));
+    }
+
+    let mut recipe_ids: Vec<String> = Vec::new();
+    for id in request.recipe_ids {
+        if store.get(&id)?.is_none() {
+            return Err(RecipeError::NotFound);
+        }
+        if !recipe_ids.contains(&id) {
+            recipe_ids.push(id);
+        }
+    }
+
+    let bucket = open_bucket()?;
+    let id = ids::uuid_v4(wasi::random::random::get_random_u64(), wasi::random::random::get_random_u64());
+    let now = current_timestamp();
+    write_collection(&bucket, &CollectionJson { id: id.clone(), name, recipe_ids, created_at: now, updated_at: now })?;
+    add_to_index(&bucket, COLLECTION_INDEX, &id)?;
+
+    Ok(id)
+}
+
+// Every collection in creation order, without loading their recipes
+fn list_collections() -> Result<Vec<CollectionSummary>, RecipeError> {
+    let bucket = open_bucket()?;
+
+    let mut collections = Vec::new();
+    for id in load_index(&bucket, COLLECTION_INDEX)? {
+        if let Some(collection) = read_collection(&bucket, &id)? {
+            collections.push(CollectionSummary {
+                id: collection.id,
+                name: collection.name,
+                recipe_count: collection.recipe_ids.len(),
+                updated_at: collection.updated_at,
+            });
+        }
+    }
+
+    Ok(collections)
+}
+
+fn expand_collection(store: &dyn RecipeStore, collection: CollectionJson) -> Result<CollectionView, RecipeError> {
+    let mut recipes = Vec::new();
+    let mut missing = Vec::new();
+    for id in collection.recipe_ids {
+        match store.get(&id)? {
+            Some(recipe) => recipes.push(RecipeView::from(recipe)),
+            None => missing.push(id),
+        }
+    }
+
+    Ok(CollectionView {
+        id: collection.id,
+        name: collection.name,
+        recipes,
+        missing,
+        created_at: collection.created_at,
+        updated_at: collection.updated_at,
+    })
+}
+
+// Ok(None) if the collection doesn't exist; a missing recipe is
+// RecipeError::NotFound. Adding a recipe that's already a member changes
+// nothing.
+fn add_to_collection(store: &dyn RecipeStore, id: &str, recipe_id: &str) -> Result<Option<CollectionJson>, RecipeError> {
+    let bucket = open_bucket()?;
+    let Some(mut collection) = read_collection(&bucket, id)? else {
+        return Ok(None);
+    };
+    if store.get(recipe_id)?.is_none() {
+        return Err(RecipeError::NotFound);
+    }
+
+    if !collection.recipe_ids.iter().any(|member| member == recipe_id) {
+        collection.recipe_ids.push(recipe_id.to_string());
+        collection.updated_at = current_timestamp();
+        write_collection(&bucket, &collection)?;
+    }
+
+    Ok(Some(collection))
+}
+
+// Ok(None) if the collection doesn't exist, Ok(Some(false)) if the recipe
+// wasn't a member
+fn remove_from_collection(id: &str, recipe_id: &str) -> Result<Option<bool>, RecipeError> {
+    let bucket = open_bucket()?;
+    let Some(mut collection) = read_collection(&bucket, id)? else {
+        return Ok(None);
+    };
+
+    let before = collection.recipe_ids.len();
+    collection.recipe_ids.retain(|member| member != recipe_id);
+    if collection.recipe_ids.len() == before {
+        return Ok(Some(false));
+    }
+
+    collection.updated_at = current_timestamp();
+    write_collection(&bucket, &collection)?;
+    Ok(Some(true))
+}
+
+// Deletes the collection only; its recipes are untouched. False if it didn't exist.
+fn delete_collection(id: &str) -> Result<bool, RecipeError> {
+    let bucket = open_bucket()?;
+    let key = collection_key(id);
+    if !bucket.exists(&key).map_err(|e| RecipeError::Storage(format!("Failed to check collection: {:?}", e)))? {
+        return Ok(false);
+    }
+
+    bucket.delete(&key).map_err(|e| RecipeError::Storage(format!("Failed to delete collection: {:?}", e)))?;
+    remove_from_index(&bucket, COLLECTION_INDEX, id)?;
+    Ok(true)
+}
+
+// Recipe storage. Handlers reach recipes, and everything kept per recipe
+// (favorites, soft-deleted copies, versions, views and ratings), only
+// through this trait, so key layout, indexes and counters stay an
+// implementation detail and another store can stand in. Collections,
+// images, idempotency records, metrics, rate limits and reindexing keep
+// their own storage.
+trait RecipeStore {
+    // Live recipes only; soft-deleted ones aren't returned
+    fn get(&self, id: &str) -> Result<Option<RecipeJson>, RecipeError>;
+
+    // IDs of every live recipe, in a stable order
+    fn list(&self) -> Result<Vec<String>, RecipeError>;
+
+    // Validates and stores a new recipe, generating an ID if it has none
+    fn create(&self, recipe: RecipeJson) -> Result<String, RecipeError>;
+
+    // Validates and overwrites an existing recipe. Returns it as stored,
+    // after unit normalization.
+    fn update(&self, id: &str, recipe: RecipeJson) -> Result<RecipeJson, RecipeError>;
+
+    // Soft delete unless `hard`; removing a missing recipe is not an error
+    fn delete(&self, id: &str, hard: bool) -> Result<(), RecipeError>;
+
+    // Whether a live recipe has `id`, without reading it
+    fn exists(&self, id: &str) -> Result<bool, RecipeError>;
+
+    // Puts a soft-deleted recipe back under its ID
+    fn restore(&self, id: &str) -> Result<RestoreOutcome, RecipeError>;
+
+    // Soft-deleted recipes that can still be restored
+    fn deleted(&self) -> Result<Vec<RecipeJson>, RecipeError>;
+
+    // IDs of favorited recipes; may name recipes deleted since
+    fn favorites(&self) -> Result<Vec<String>, RecipeError>;
+
+    // RecipeError::NotFound if there's no live recipe with `id`
+    fn set_favorite(&self, id: &str, favorite: bool) -> Result<(), RecipeError>;
+
+    // Retained earlier versions, newest first
+    fn versions(&self, id: &str) -> Result<Vec<VersionSummary>, RecipeError>;
+
+    fn version(&self, id: &str, version: u64) -> Result<Option<RecipeJson>, RecipeError>;
+
+    // Counts one fetch of the recipe; concurrent calls mustn't lose counts
+    fn record_view(&self, id: &str) -> Result<(), RecipeError>;
+
+    // Fetches counted so far, 0 for a recipe never fetched
+    fn views(&self, id: &str) -> Result<u64, RecipeError>;
+
+    // Adds a 1-5 rating to the recipe's totals and returns them
+    fn rate(&self, id: &str, rating: u64) -> Result<RatingSummary, RecipeError>;
+
+    // Zero totals for a recipe never rated
+    fn rating(&self, id: &str) -> Result<RatingSummary, RecipeError>;
+
+    // Ok if the store is reachable, for readiness checks
+    fn ping(&self) -> Result<(), RecipeError>;
+}
+
+// RecipeStore backed by the `recipes` wasi:keyvalue bucket, opened per call
+// so routes that never touch storage never open it.
+//
+// Listing enumerates `recipe:` keys directly, so it can't drift from what
+// is actually stored, and returns them sorted by ID. Only when that scan
+// fails does it fall back to the `_recipe_ids` index, which writes maintain
+// only on backends that can't list keys.
+struct KeyValueStore;
+
+impl RecipeStore for KeyValueStore {
+    fn get(&self, id: &str) -> Result<Option<RecipeJson>, RecipeError> {
+        read_recipe(&self.bucket()?, &format!("recipe:{}", id))
+    }
+
+    fn list(&self) -> Result<Vec<String>, RecipeError> {
+        let bucket = self.bucket()?;
+
+        match self.scan_recipe_ids(&bucket) {
+            Ok(mut ids) => {
+                ids.sort_unstable();
+                Ok(ids)
+            }
+            Err(e) => {
+                log(Level::Warn, "recipe-api", &format!("Key listing unavailable, using index: {}", e));
+                load_index(&bucket, RECIPE_INDEX)
+            }
+        }
+    }
+
+    fn create(&self, recipe: RecipeJson) -> Result<String, RecipeError> {
+        let bucket = self.bucket()?;
+
+        let id = store_new_recipe(&bucket, recipe)?;
+        if !key_listing_available(&bucket) {
+            add_to_index(&bucket, RECIPE_INDEX, &id)?;
+        }
+
+        Ok(id)
+    }
+
+    fn update(&self, id: &str, mut recipe: RecipeJson) -> Result<RecipeJson, RecipeError> {
+        let bucket = self.bucket()?;
+
+        let key = format!("recipe:{}", id);
+        let previous = bucket.get(&key).map_err(|e| RecipeError::Storage(format!("Failed to get recipe: {:?}", e)))?;
+        // A previous version that no longer parses just isn't compared against
+        let previous_recipe = previous.as_deref().and_then(|data| serde_json::from_slice::<RecipeJson>(data).ok());
+        prepare_recipe(&mut recipe, previous_recipe.as_ref())?;
+
+        if let Some(previous) = previous {
+            snapshot_version(&bucket, id, &previous)?;
+        }
+
+        let data = serde_json::to_vec(&recipe).map_err(|e| RecipeError::Serialization(format!("Failed to serialize: {:?}", e)))?;
+        bucket.set(&key, &data).map_err(|e| RecipeError::Storage(format!("Failed to update recipe: {:?}", e)))?;
+
+        Ok(recipe)
+    }
+
+    fn delete(&self, id: &str, hard: bool) -> Result<(), RecipeError> {
+        remove_recipe(&self.bucket()?, id, hard)
+    }
+
+    fn exists(&self, id: &str) -> Result<bool, RecipeError> {
+        self.bucket()?
+            .exists(&format!("recipe:{}", id))
+            .map_err(|e| RecipeError::Storage(format!("Failed to check recipe: {:?}", e)))
+    }
+
+    fn restore(&self, id: &str) -> Result<RestoreOutcome, RecipeError> {
+        restore_recipe(&self.bucket()?, id)
+    }
+
+    fn deleted(&self) -> Result<Vec<RecipeJson>, RecipeError> {
+        load_deleted_recipes(&self.bucket()?)
+    }
+
+    fn favorites(&self) -> Result<Vec<String>, RecipeError> {
+        load_index(&self.bucket()?, FAVORITES_INDEX)
+    }
+
+    fn set_favorite(&self, id: &str, favorite: bool) -> Result<(), RecipeError> {
+        set_favorite(&self.bucket()?, id, favorite)
+    }
+
+    fn versions(&self, id: &str) -> Result<Vec<VersionSummary>, RecipeError> {
+        list_versions(&self.bucket()?, id)
+    }
+
+    fn version(&self, id: &str, version: u64) -> Result<Option<RecipeJson>, RecipeError> {
+        read_recipe(&self.bucket()?, &version_key(id, version))
+    }
+
+    fn record_view(&self, id: &str) -> Result<(), RecipeError> {
+        increment(&self.bucket()?, &views_key(id), 1)
+            .map(|_| ())
+            .map_err(|e| RecipeError::Storage(format!("Failed to count view: {:?}", e)))
+    }
+
+    fn views(&self, id: &str) -> Result<u64, RecipeError> {
+        let bucket = self.bucket()?;
+        let key = views_key(id);
+        // Checked first so reading doesn't create a counter for every recipe
+        if !bucket.exists(&key).map_err(|e| RecipeError::Storage(format!("Failed to check views: {:?}", e)))? {
+            return Ok(0);
+        }
+        increment(&bucket, &key, 0).map_err(|e| RecipeError::Storage(format!("Failed to read views: {:?}", e)))
+    }
+
+    fn rate(&self, id: &str, rating: u64) -> Result<RatingSummary, RecipeError> {
+        add_rating(&self.bucket()?, id, rating)
+    }
+
+    fn rating(&self, id: &str) -> Result<RatingSummary, RecipeError> {
+        load_rating(&self.bucket()?, id)
+    }
+
+    fn ping(&self) -> Result<(), RecipeError> {
+        self.bucket().map(|_| ())
+    }
+}
+
+impl KeyValueStore {
+    fn bucket(&self) -> Result<Bucket, RecipeError> {
+        open_bucket()
+    }
+
+    // keyvalue's list-keys has no prefix filter, so every key is paged
+    // through and only `recipe:{id}` ones are kept
+    fn scan_recipe_ids(&self, bucket: &Bucket) -> Result<Vec<String>, RecipeError> {
+        let mut ids = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let page = bucket
+                .list_keys(cursor)
+                .map_err(|e| RecipeError::Storage(format!("Failed to list keys: {:?}", e)))?;
+            ids.extend(page.keys.iter().filter_map(|key| recipe_id_from_key(key)));
+
+            match page.cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(ids)
+    }
+}
+
+// True if the bucket can list keys, checked once per instance by asking for
+// the first page. While it can, recipes are listed by scanning, so writes
+// skip `_recipe_ids`; reindex brings it up to date before moving to a
+// backend without listing.
+fn key_listing_available(bucket: &Bucket) -> bool {
+    KEY_LISTING.with(|listing| *listing.get_or_init(|| bucket.list_keys(None).is_ok()))
+}
+
+// Rebuild `_recipe_ids` to match the stored recipes: IDs with a `recipe:`
+// key but no slot are added, and slots naming a missing recipe are freed, as
+// are extra slots of an ID held twice. Without key listing only the pruning
+// is possible, so nothing is added.
+fn reindex_recipes() -> Result<ReindexSummary, RecipeError> {
+    let bucket = open_bucket()?;
+    free_duplicate_slots(&bucket, RECIPE_INDEX, &load_index_slots(&bucket, RECIPE_INDEX)?)?;
+    let indexed = load_index(&bucket, RECIPE_INDEX)?;
+
+    let (added, removed) = match KeyValueStore.scan_recipe_ids(&bucket) {
+        Ok(stored) => {
+            let indexed_set: HashSet<&str> = indexed.iter().map(String::as_str).collect();
+            let stored_set: HashSet<&str> = stored.iter().map(String::as_str).collect();
+            let added: Vec<String> = stored.iter().filter(|id| !indexed_set.contains(id.as_str())).cloned().collect();
+            let removed: Vec<String> = indexed.iter().filter(|id| !stored_set.contains(id.as_str())).cloned().collect();
+            (added, removed)
+        }
+        Err(e) => {
+            log(Level::Warn, "recipe-api", &format!("Key listing unavailable, only pruning index: {}", e));
+            let mut removed = Vec::new();
+            for id in &indexed {
+                let exists = bucket
+                    .exists(&format!("recipe:{}", id))
+                    .map_err(|e| RecipeError::Storage(format!("Failed to check recipe: {:?}", e)))?;
+                if !exists {
+                    removed.push(id.clone());
+                }
+            }
+            (Vec::new(), removed)
+        }
+    };
+
+    // A missing slot may still be claimed, if the add that claimed it failed
+    // before writing the slot
+    for id in &added {
+        BucketOps::delete(&bucket, &claim_key(RECIPE_INDEX, id))?;
+    }
+    add_many_to_index(&bucket, RECIPE_INDEX, &added)?;
+    for id in &removed {
+        remove_from_index(&bucket, RECIPE_INDEX, id)?;
+    }
+
+    let total = indexed.len() + added.len() - removed.len();
+    Ok(ReindexSummary { added, removed, total })
+}
+
+// `recipe:{id}` holds a live recipe; version snapshots and counters share the
+// prefix as `recipe:{id}:v{n}` and `recipe:{id}:versions` and are skipped
+fn recipe_id_from_key(key: &str) -> Option<String> {
+    let id = key.strip_prefix("recipe:")?;
+
+    if let Some((_, suffix)) = id.rsplit_once(':') {
+        let is_version = suffix
+            .strip_prefix('v')
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+        if is_version || suffix == "versions" {
+            return None;
+        }
+    }
+
+    Some(id.to_string())
+}
+
+// The bucket calls the slot indexes make, so their bookkeeping can be
+// exercised against an in-memory map in tests
+trait BucketOps {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, RecipeError>;
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), RecipeError>;
+    fn delete(&self, key: &str) -> Result<(), RecipeError>;
+    fn exists(&self, key: &str) -> Result<bool, RecipeError>;
+    fn increment(&self, key: &str, delta: u64) -> Result<u64, RecipeError>;
+}
+
+impl BucketOps for Bucket {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, RecipeError> {
+        Bucket::get(self, key).map_err(|e| RecipeError::Storage(format!("Failed to get {}: {:?}", key, e)))
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), RecipeError> {
+        Bucket::set(self, key, value).map_err(|e| RecipeError::Storage(format!("Failed to set {}: {:?}", key, e)))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), RecipeError> {
+        Bucket::delete(self, key).map_err(|e| RecipeError::Storage(format!("Failed to delete {}: {:?}", key, e)))
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, RecipeError> {
+        Bucket::exists(self, key).map_err(|e| RecipeError::Storage(format!("Failed to check {}: {:?}", key, e)))
+    }
+
+    fn increment(&self, key: &str, delta: u64) -> Result<u64, RecipeError> {
+        increment(self, key, delta).map_err(|e| RecipeError::Storage(format!("Failed to increment {}: {:?}", key, e)))
+    }
+}
+
+// Indexes (recipe IDs, favorites) are one key per slot rather than a shared
+// JSON list, so concurrent writers never overwrite each other:
+//
+//   {index}:next        atomic counter of allocated slots
+//   {index}:{slot}      ID held in that slot
+//   {index}:id:{id}     slot holding the ID, for membership and removal
+//   {index}:claim:{id}  atomic counter deciding which writer adds the ID
+//
+// Slots are allocated with an atomic increment, so two concurrent writers
+// always land in different slots and listing preserves insertion order.
+// Writers adding the same ID both bump its claim, and only the one that
+// takes it from 0 to 1 allocates a slot, so an ID never holds two slots and
+// a single removal always unlists it.
+fn load_index(kv: &dyn BucketOps, index: &str) -> Result<Vec<String>, RecipeError> {
+    let mut seen = HashSet::new();
+    Ok(load_index_slots(kv, index)?
+        .into_iter()
+        .map(|(_, id)| id)
+        .filter(|id| seen.insert(id.clone()))
+        .collect())
+}
+
+// Every filled slot in slot order. Before claims, concurrent adds could put
+// one ID in two slots, so an ID may appear more than once.
+fn load_index_slots(kv: &dyn BucketOps, index: &str) -> Result<Vec<(u64, String)>, RecipeError> {
+    // Incrementing by zero reads the slot counter atomically
+    let last_slot = kv.increment(&format!("{}:next", index), 0)?;
+
+    let mut slots = Vec::new();
+    for slot in 1..=last_slot {
+        // Removed entries leave empty slots behind
+        if let Some(data) = kv.get(&format!("{}:{}", index, slot))? {
+            let id = String::from_utf8(data).map_err(|e| RecipeError::Serialization(format!("Invalid UTF-8: {:?}", e)))?;
+            slots.push((slot, id));
+        }
+    }
+
+    Ok(slots)
+}
+
+// Of an ID's slots, keep the one its marker points at (or the first, if the
+// marker is missing or stale) and free the rest
+fn free_duplicate_slots(kv: &dyn BucketOps, index: &str, slots: &[(u64, String)]) -> Result<(), RecipeError> {
+    let mut held: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+    for (slot, id) in slots {
+        held.entry(id.as_str()).or_default().push(*slot);
+    }
+
+    for (id, id_slots) in held {
+        let [first, ..] = id_slots[..] else { continue };
+        if id_slots.len() == 1 {
+            continue;
+        }
+
+        let marker_key = format!("{}:id:{}", index, id);
+        let marked = kv
+            .get(&marker_key)?
+            .and_then(|data| String::from_utf8(data).ok())
+            .and_then(|slot| slot.parse::<u64>().ok());
+        let keep = marked.filter(|slot| id_slots.contains(slot)).unwrap_or(first);
+
+        kv.set(&marker_key, keep.to_string().as_bytes())?;
+        for slot in id_slots.into_iter().filter(|&slot| slot != keep) {
+            kv.delete(&format!("{}:{}", index, slot))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn claim_key(index: &str, id: &str) -> String {
+    format!("{}:claim:{}", index, id)
+}
+
+fn index_contains(kv: &dyn BucketOps, index: &str, id: &str) -> Result<bool, RecipeError> {
+    kv.exists(&format!("{}:id:{}", index, id))
+}
+
+fn add_to_index(kv: &dyn BucketOps, index: &str, id: &str) -> Result<(), RecipeError> {
+    let held = PENDING_INDEX_ADDS.with(|pending| match pending.borrow_mut().as_mut() {
+        Some(adds) => {
+            adds.push((index.to_string(), id.to_string()));
+            true
+        }
+        None => false,
+    });
+    if held {
+        return Ok(());
+    }
+
+    add_many_to_index(kv, index, &[id.to_string()])
+}
+
+// Runs a batch endpoint's writes with index additions held in memory, then
+// applies them with one slot allocation per index rather than one per item.
+// Held additions are flushed even if `writes` fails, so recipes it already
+// stored stay listed. Until then, index_contains doesn't see them. Removals
+// only touch the ID's own keys, so there's nothing to gain by holding them.
+fn with_index_batch<T>(writes: impl FnOnce() -> Result<T, RecipeError>) -> Result<T, RecipeError> {
+    PENDING_INDEX_ADDS.with(|pending| *pending.borrow_mut() = Some(Vec::new()));
+    let result = writes();
+    let flushed = flush_index_batch();
+    PENDING_INDEX_ADDS.with(|pending| *pending.borrow_mut() = None);
+
+    flushed?;
+    result
+}
+
+// Write the additions held so far and keep batching. Long-running batches
+// call this every so often so the held IDs stay bounded.
+fn flush_index_batch() -> Result<(), RecipeError> {
+    let adds = PENDING_INDEX_ADDS.with(|pending| pending.borrow_mut().as_mut().map(std::mem::take)).unwrap_or_default();
+    if adds.is_empty() {
+        return Ok(());
+    }
+
+    // Claims would skip repeats anyway; grouping just saves the round trips
+    let mut by_index: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    for (index, id) in adds {
+        if seen.insert((index.clone(), id.clone())) {
+            by_index.entry(index).or_default().push(id);
+        }
+    }
+
+    let bucket = open_bucket()?;
+    for (index, ids) in by_index {
+        add_many_to_index(&bucket, &index, &ids)?;
+    }
+    Ok(())
+}
+
+// Add several IDs at once, reserving all their slots with a single increment
+fn add_many_to_index(kv: &dyn BucketOps, index: &str, ids: &[String]) -> Result<(), RecipeError> {
+    let mut new_ids = Vec::new();
+    for id in ids {
+        // IDs indexed before claims existed have a marker but no claim
+        if index_contains(kv, index, id)? {
+            continue;
+        }
+        if kv.increment(&claim_key(index, id), 1)? == 1 {
+            new_ids.push(id);
+        }
+    }
+
+    if new_ids.is_empty() {
+        return Ok(());
+    }
+
+    let last_slot = kv.increment(&format!("{}:next", index), new_ids.len() as u64)?;
+    let first_slot = last_slot + 1 - new_ids.len() as u64;
+
+    for (slot, id) in (first_slot..=last_slot).zip(new_ids) {
+        kv.set(&format!("{}:{}", index, slot), id.as_bytes())?;
+        kv.set(&format!("{}:id:{}", index, id), slot.to_string().as_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn remove_from_index(kv: &dyn BucketOps, index: &str, id: &str) -> Result<(), RecipeError> {
+    let marker_key = format!("{}:id:{}", index, id);
+
+    if let Some(slot) = kv.get(&marker_key)? {
+        let slot = String::from_utf8(slot).map_err(|e| RecipeError::Serialization(format!("Invalid UTF-8: {:?}", e)))?;
+        kv.delete(&format!("{}:{}", index, slot))?;
+        kv.delete(&marker_key)?;
+    }
+    // Released last, so the ID can't be claimed again while it still has a slot
+    kv.delete(&claim_key(index, id))?;
+
+    Ok(())
+}
+
+// First value of a request header, if present and valid UTF-8
+fn header_value(headers: &Fields, name: &str) -> Option<String> {
+    headers
+        .get(&name.to_string())
+        .into_iter()
+        .next()
+        .and_then(|value| String::from_utf8(value).ok())
+}
+
+// Bodies are always parsed as JSON, so a missing Content-Type is accepted.
+// Parameters such as `; charset=utf-8` are ignored.
+fn has_json_content_type(headers: &Fields) -> bool {
+    media_type(headers).is_none_or(|media_type| media_type == "application/json")
+}
+
+// Lowercased Content-Type without parameters
+fn media_type(headers: &Fields) -> Option<String> {
+    let value = header_value(headers, "content-type")?;
+    Some(value.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+}
+
+// Strong ETag over the canonical serialization, so identical content always
+// yields the same tag. Rating totals are part of what GET serves, so they're
+// hashed in too; an unrated recipe keeps the tag of its record alone.
+fn recipe_etag(recipe: &RecipeJson, rating: Option<&RatingSummary>) -> String {
+    let mut bytes = serde_json::to_vec(recipe).unwrap_or_default();
+    if let Some(rating) = rating.filter(|rating| rating.rating_count > 0) {
+        bytes.extend_from_slice(format!("|rating:{}/{}", rating.sum, rating.rating_count).as_bytes());
+    }
+    format!("\"{:016x}\"", fnv1a_hash(&bytes))
+}
+
+// FNV-1a is stable across builds, unlike std's randomly keyed hasher
+fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Compare an If-None-Match / If-Match header, which may list several tags
+// or be `*`, against the current tag. Weak validators compare by value.
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+// If-None-Match takes precedence; If-Modified-Since is only consulted
+// without it. Dates we can't read are ignored rather than rejected.
+fn not_modified(headers: &Fields, etag: &str, updated_at: u64) -> bool {
+    if let Some(tags) = header_value(headers, "if-none-match") {
+        return etag_matches(&tags, etag);
+    }
+    header_value(headers, "if-modified-since")
+        .and_then(|date| http_date::parse(&date))
+        .is_some_and(|since| updated_at <= since)
+}
+
+fn read_request_body(request: IncomingRequest) -> Result<Vec<u8>, String> {
+    let body = request.consume().map_err(|_| "Failed to consume body")?;
+    let stream = body.stream().map_err(|_| "Failed to get stream")?;
+
+    let mut result = Vec::new();
+    loop {
+        match stream.blocking_read(4096) {
+            Ok(chunk) => {
+                if chunk.is_empty() {
+                    break;
+                }
+                result.extend_from_slice(&chunk);
+            }
+            Err(_) => break,
+        }
+    }
+
+    Ok(result)
+}
+
+// Nothing but whitespace, so there's no JSON to parse. JSON bodies are
+// checked for this first, since serde's "EOF while parsing" error doesn't tell
+// the client the body is missing.
+fn is_blank(body: &[u8]) -> bool {
+    body.iter().all(u8::is_ascii_whitespace)
+}
+
+// Reads and deserializes a required JSON body. On failure the 400 has
+// already been sent and None comes back; otherwise the outparam is handed
+// back along with the value.
+fn read_json_body<T: serde::de::DeserializeOwned>(
+    request: IncomingRequest,
+    response_out: ResponseOutparam,
+) -> Option<(T, ResponseOutparam)> {
+    read_body_with(request, response_out, |body| serde_json::from_slice::<T>(body))
+}
+
+// A create or update body, see parse_recipe
+fn read_recipe_body(request: IncomingRequest, response_out: ResponseOutparam) -> Option<(RecipeJson, ResponseOutparam)> {
+    read_body_with(request, response_out, parse_recipe)
+}
+
+// read_json_body with the deserialization step supplied by the caller
+fn read_body_with<T>(
+    request: IncomingRequest,
+    response_out: ResponseOutparam,
+    parse: impl FnOnce(&[u8]) -> Result<T, serde_json::Error>,
+) -> Option<(T, ResponseOutparam)> {
+    match read_request_body(request) {
+        Ok(body) if is_blank(&body) => {
+            send_response(400, b"Request body is required", response_out);
+            None
+        }
+        Ok(body) => match parse(&body) {
+            Ok(value) => Some((value, response_out)),
+            Err(e) => {
+                log(Level::Error, "recipe-api", &format!("Invalid JSON: {:?}", e));
+                send_invalid_json(&e, response_out);
+                None
+            }
+        },
+        Err(_) => {
+            send_response(400, b"Failed to read body", response_out);
+            None
+        }
+    }
+}
+
+// Like read_request_body, but stops reading once the body passes `limit`
+// bytes and returns None
+fn read_request_body_capped(request: IncomingRequest, limit: usize) -> Result<Option<Vec<u8>>, String> {
+    let body = request.consume().map_err(|_| "Failed to consume body")?;
+    let stream = body.stream().map_err(|_| "Failed to get stream")?;
+
+    let mut result = Vec::new();
+    while let Ok(chunk) = stream.blocking_read(4096) {
+        if chunk.is_empty() {
+            break;
+        }
+        if result.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        result.extend_from_slice(&chunk);
+    }
+
+    Ok(Some(result))
+}
+
+enum Line {
+    Text(Vec<u8>),
+    // The line passed MAX_NDJSON_LINE_BYTES; the rest of it was skipped
+    TooLong,
+}
+
+// Splits a request body into lines as it is read, holding at most one line
+// and one read's worth of bytes. A read error ends the lines early and is
+// left in `error`.
+struct LineReader {
+    // Declared before `_body` so the stream is dropped first
+    stream: InputStream,
+    _body: IncomingBody,
+    buffer: Vec<u8>,
+    // Bytes of `buffer` already searched for a newline
+    scanned: usize,
+    skipping: bool,
+    done: bool,
+    error: Option<String>,
+}
+
+impl LineReader {
+    fn new(request: IncomingRequest) -> Result<Self, String> {
+        let body = request.consume().map_err(|_| "Failed to consume body")?;
+        let stream = body.stream().map_err(|_| "Failed to get stream")?;
+        Ok(LineReader { stream, _body: body, buffer: Vec::new(), scanned: 0, skipping: false, done: false, error: None })
+    }
+
+    // The next line without its `\n` (or `\r\n`), or None at the end of the body
+    fn next_line(&mut self) -> Option<Line> {
+        loop {
+            if let Some(offset) = self.buffer[self.scanned..].iter().position(|&b| b == b'\n') {
+                let end = self.scanned + offset;
+                let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+                self.scanned = 0;
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                if std::mem::take(&mut self.skipping) {
+                    return Some(Line::TooLong);
+                }
+                return Some(Line::Text(line));
+            }
+            self.scanned = self.buffer.len();
+
+            if self.buffer.len() > MAX_NDJSON_LINE_BYTES {
+                self.buffer.clear();
+                self.scanned = 0;
+                self.skipping = true;
+            }
+
+            if self.done {
+                // A last line without a trailing newline
+                if self.skipping {
+                    self.skipping = false;
+                    return Some(Line::TooLong);
+                }
+                if self.buffer.is_empty() {
+                    return None;
+                }
+                self.scanned = 0;
+                return Some(Line::Text(std::mem::take(&mut self.buffer)));
+            }
+
+            match self.stream.blocking_read(4096) {
+                Ok(chunk) if !chunk.is_empty() => self.buffer.extend_from_slice(&chunk),
+                Ok(_) | Err(wasi::io::streams::StreamError::Closed) => self.done = true,
+                Err(e) => {
+                    self.error = Some(format!("Failed to read body: {:?}", e));
+                    self.done = true;
+                    self.buffer.clear();
+                    self.skipping = false;
+                }
+            }
+        }
+    }
+}
+
+// The response for a failed storage call. Only server-side failures are
+// logged, after `context` saying what was being attempted.
+fn send_error(context: &str, e: RecipeError, response_out: ResponseOutparam) {
+    let status = e.status();
+    match e {
+        RecipeError::Validation(problems) => send_validation_errors(&problems, response_out),
+        RecipeError::NotFound => send_response(status, b"Recipe not found", response_out),
+        RecipeError::Conflict => send_response(status, b"A recipe with this ID already exists", response_out),
+        RecipeError::Storage(_) => {
+            log(Level::Error, "recipe-api", &format!("{}: {}", context, e));
+            send_response(status, b"Storage unavailable", response_out);
+        }
+        RecipeError::Serialization(_) => {
+            log(Level::Error, "recipe-api", &format!("{}: {}", context, e));
+            send_response(status, b"Internal Server Error", response_out);
+        }
+    }
+}
+
+fn send_validation_errors(problems: &[String], response_out: ResponseOutparam) {
+    let json = serde_json::json!({ "errors": problems }).to_string();
+    send_json_response(400, json.as_bytes(), response_out);
+}
+
+// Methods each known resource supports, used for `Allow` headers.
+// Keep in step with the handle_* route arms.
+fn allowed_methods(path: &[&str]) -> Option<&'static str> {
+    match path {
+        ["health"] | ["health", "live" | "ready"] => Some("GET, OPTIONS"),
+        ["metrics"] => Some("GET, OPTIONS"),
+        ["api", "shopping-list"] => Some("POST, OPTIONS"),
+        ["api", "admin", "reindex"] => Some("POST, OPTIONS"),
+        ["api", "collections"] => Some("GET, POST, OPTIONS"),
+        ["api", "collections", _] => Some("GET, DELETE, OPTIONS"),
+        ["api", "collections", _, "recipes"] => Some("POST, OPTIONS"),
+        ["api", "collections", _, "recipes", _] => Some("DELETE, OPTIONS"),
+        ["api", "recipes"] => Some("GET, POST, OPTIONS"),
+        ["api", "recipes", "search" | "count" | "export" | "deleted" | "tags" | "diets" | "random" | "schema" | "grouped" | "popular" | "quick"] => {
+            Some("GET, OPTIONS")
+        }
+        ["api", "recipes", "bulk" | "import" | "batch-delete" | "makeable"] => Some("POST, OPTIONS"),
+        ["api", "recipes", "difficulty" | "by-ingredient", _] => Some("GET, OPTIONS"),
+        ["api", "recipes", _] => Some("GET, HEAD, PUT, PATCH, DELETE, OPTIONS"),
+        ["api", "recipes", _, "scaled" | "nutrition" | "timeline" | "versions" | "related" | "cook"] => Some("GET, OPTIONS"),
+        ["api", "recipes", _, "image"] => Some("GET, PUT, OPTIONS"),
+        ["api", "recipes", _, "versions", _] => Some("GET, OPTIONS"),
+        ["api", "recipes", _, "tags"] => Some("POST, OPTIONS"),
+        ["api", "recipes", _, "steps", "reorder"] => Some("POST, OPTIONS"),
+        ["api", "recipes", _, "tags", _] => Some("DELETE, OPTIONS"),
+        ["api", "recipes", _, "favorite"] => Some("POST, DELETE, OPTIONS"),
+        ["api", "recipes", _, "ratings"] => Some("POST, OPTIONS"),
+        ["api", "recipes", _, "duplicate" | "restore" | "scale-copy"] => Some("POST, OPTIONS"),
+        _ => None,
+    }
+}
+
+// Serves both CORS preflights and tooling that discovers methods per resource
+fn handle_options(path: &[&str], response_out: ResponseOutparam) {
+    match allowed_methods(path) {
+        Some(allow) => send_response_with_headers(204, b"", &[("allow", allow)], response_out),
+        None => send_response(404, b"Not Found", response_out),
+    }
+}
+
+// Fallthrough for the method handlers: 405 with `Allow` if the resource
+// exists under other methods, 404 otherwise
+fn send_no_route(path: &[&str], response_out: ResponseOutparam) {
+    match allowed_methods(path) {
+        Some(allow) => send_response_with_headers(405, b"Method Not Allowed", &[("allow", allow)], response_out),
+        None => send_response(404, b"Not Found", response_out),
+    }
+}
+
+// 400 for a body that didn't deserialize, pointing at where parsing stopped.
+// serde's message names the problem, e.g. "unknown field `serving`, expected
+// one of ..."; its " at line N column M" suffix is split out into fields.
+fn send_invalid_json(e: &serde_json::Error, response_out: ResponseOutparam) {
+    let message = e.to_string();
+    let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(message, _)| message);
+    let mut error = serde_json::json!({ "code": "invalid_json", "message": message });
+    // Errors found after a body was parsed into a Value have no position
+    if e.line() > 0 {
+        error["line"] = serde_json::json!(e.line());
+        error["column"] = serde_json::json!(e.column());
+    }
+    let json = serde_json::json!({ "error": error }).to_string();
+    send_json_response(400, json.as_bytes(), response_out);
+}
+
+fn send_unsupported_media_type(response_out: ResponseOutparam) {
+    send_response(415, b"Unsupported Media Type: expected application/json", response_out);
+}
+
+// 200 with the would-be stored recipe; nothing has been written
+fn send_dry_run(preview: Result<RecipeJson, RecipeError>, response_out: ResponseOutparam) {
+    match preview {
+        Ok(recipe) => {
+            send_json(200, &recipe, response_out);
+        }
+        Err(e) => {
+            send_error("Error previewing recipe", e, response_out);
+        }
+    }
+}
+
+// 201 for a new recipe, with a Location the client can GET. Any unit
+// warnings ride along in the body.
+fn send_created(id: &str, warnings: &[String], response_out: ResponseOutparam) {
+    let location = format!("/api/recipes/{}", id);
+    let mut response = serde_json::json!({ "id": id });
+    if !warnings.is_empty() {
+        response["warnings"] = serde_json::json!(warnings);
+    }
+    send_json_response_with_headers(201, response.to_string().as_bytes(), &[("location", location.as_str())], response_out);
+}
+
+// 412 carrying the recipe's current ETag (if it exists) so the client can refetch
+fn send_precondition_failed(current_etag: Option<&str>, response_out: ResponseOutparam) {
+    let json = serde_json::json!({
+        "error": "Precondition Failed",
+        "etag": current_etag,
+    })
+    .to_string();
+
+    match current_etag {
+        Some(etag) => send_json_response_with_headers(412, json.as_bytes(), &[("etag", etag)], response_out),
+        None => send_json_response(412, json.as_bytes(), response_out),
+    }
+}
+
+fn send_response(status: u16, body: &[u8], response_out: ResponseOutparam) {
+    send_response_with_headers(status, body, &[], response_out);
+}
+
+fn send_response_with_headers(status: u16, body: &[u8], extra_headers: &[(&str, &str)], response_out: ResponseOutparam) {
+    let headers = response_headers();
+    for (name, value) in extra_headers {
+        set_header(&headers, name, value.as_bytes());
+    }
+
+    write_response(status, body, headers, response_out);
+}
+
+// Serialize `value` as the JSON body, or answer 500 if it can't be
+fn send_json<T: Serialize>(status: u16, value: &T, response_out: ResponseOutparam) {
+    send_json_with_headers(status, value, &[], response_out);
+}
+
+fn send_json_with_headers<T: Serialize>(status: u16, value: &T, extra_headers: &[(&str, &str)], response_out: ResponseOutparam) {
+    match serde_json::to_string(value) {
+        Ok(json) => send_json_response_with_headers(status, json.as_bytes(), extra_headers, response_out),
+        Err(e) => {
+            log(Level::Error, "recipe-api", &format!("Failed to serialize response: {}", e));
+            send_response(500, b"Internal Server Error", response_out);
+        }
+    }
+}
+
+fn to_json_value<T: Serialize>(value: &T) -> Result<serde_json::Value, RecipeError> {
+    serde_json::to_value(value).map_err(|e| RecipeError::Serialization(format!("Failed to serialize: {:?}", e)))
+}
+
+fn send_json_response(status: u16, body: &[u8], response_out: ResponseOutparam) {
+    send_json_response_with_headers(status, body, &[], response_out);
+}
+
+fn send_json_response_with_headers(status: u16, body: &[u8], extra_headers: &[(&str, &str)], response_out: ResponseOutparam) {
+    send_typed_response(status, body, "application/json", extra_headers, response_out);
+}
+
+// Serialize `value` in the negotiated format. RecipeJson stays the single
+// source of truth; only the serializer changes.
+fn send_formatted<T: Serialize>(
+    status: u16,
+    value: &T,
+    format: ResponseFormat,
+    extra_headers: &[(&str, &str)],
+    response_out: ResponseOutparam,
+) {
+    let mut headers = extra_headers.to_vec();
+    headers.push(("vary", "Accept"));
+
+    let serialized = match format {
+        ResponseFormat::Json | ResponseFormat::Html => {
+            serde_json::to_string(value).map(|json| (json, "application/json")).map_err(|e| e.to_string())
+        }
+        ResponseFormat::Yaml => yaml::to_string(value).map(|yaml| (yaml, "application/yaml")).map_err(|e| e.to_string()),
+        ResponseFormat::Ndjson => serde_json::to_string(value)
+            .map(|json| (json + "\n", "application/x-ndjson"))
+            .map_err(|e| e.to_string()),
+    };
+
+    match serialized {
+        Ok((body, content_type)) => send_typed_response(status, body.as_bytes(), content_type, &headers, response_out),
+        Err(e) => {
+            log(Level::Error, "recipe-api", &format!("Failed to serialize response: {}", e));
+            send_response(500, b"Internal Server Error", response_out);
+        }
+    }
+}
+
+fn send_typed_response(
+    status: u16,
+    body: &[u8],
+    content_type: &str,
+    extra_headers: &[(&str, &str)],
+    response_out: ResponseOutparam,
+) {
+    let headers = response_headers();
+    set_header(&headers, "content-type", content_type.as_bytes());
+    append_header(&headers, "vary", b"Accept-Encoding");
+    for (name, value) in extra_headers {
+        // Vary accumulates; everything else replaces
+        if *name == "vary" {
+            append_header(&headers, name, value.as_bytes());
+        } else {
+            set_header(&headers, name, value.as_bytes());
+        }
+    }
+
+    // Compress large bodies when the client accepts gzip
+    let compressed = if body.len() >= GZIP_MIN_BYTES && REQUEST_CONTEXT.with(|ctx| ctx.borrow().accepts_gzip) {
+        gzip(body)
+            .map_err(|e| log(Level::Warn, "recipe-api", &format!("Failed to gzip response: {:?}", e)))
+            .ok()
+    } else {
+        None
+    };
+
+    match compressed {
+        Some(gzipped) => {
+            set_header(&headers, "content-encoding", b"gzip");
+            write_response(status, &gzipped, headers, response_out);
+        }
+        None => write_response(status, body, headers, response_out),
+    }
+}
+
+fn gzip(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
+
+// True if an Accept-Encoding header allows gzip, honouring `q=0` to refuse it
+fn accepts_gzip(header: &str) -> bool {
+    header.split(',').any(|coding| {
+        let mut params = coding.split(';').map(str::trim);
+        let name = params.next().unwrap_or("");
+        let refused = params.any(|p| {
+            p.strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+        name.eq_ignore_ascii_case("gzip") && !refused
+    })
+}
+
+// Never panics: a trap would drop the response entirely, so failures are
+// logged and the client gets the closest response we can still send
+fn write_response(status: u16, body: &[u8], headers: Fields, response_out: ResponseOutparam) {
+    let response = OutgoingResponse::new(headers);
+    let status = set_status(&response, status);
+
+    match write_body(&response, body) {
+        Ok(()) => {
+            REQUEST_CONTEXT.with(|ctx| ctx.borrow_mut().status = Some(status));
+            ResponseOutparam::set(response_out, Ok(response));
+        }
+        Err(e) => {
+            // The host answers with a 500 of its own
+            log(Level::Error, "recipe-api", &e);
+            REQUEST_CONTEXT.with(|ctx| ctx.borrow_mut().status = Some(500));
+            ResponseOutparam::set(response_out, Err(ErrorCode::InternalError(Some(e))));
+        }
+    }
+}
+
+// Sets the status, falling back to 500 if the host rejects it anyway
+fn set_status(response: &OutgoingResponse, status: u16) -> u16 {
+    let effective = effective_status(status);
+    if effective != status {
+        log(Level::Error, "recipe-api", &format!("Invalid status code {}, sending 500", status));
+    }
+    if response.set_status_code(effective).is_ok() {
+        return effective;
+    }
+
+    log(Level::Error, "recipe-api", &format!("Status code {} rejected, sending 500", effective));
+    // 500 is always a valid code
+    let _ = response.set_status_code(500);
+    500
+}
+
+// Codes outside 100-999 are rejected by the host; send those as a 500
+fn effective_status(status: u16) -> u16 {
+    if (100..=999).contains(&status) {
+        status
+    } else {
+        500
+    }
+}
+
+fn write_body(response: &OutgoingResponse, body: &[u8]) -> Result<(), String> {
+    let response_body = response.body().map_err(|_| "Failed to get response body".to_string())?;
+
+    // 204 and 304 have no body to write, only one to finish
+    if !body.is_empty() {
+        let stream = response_body
+            .write()
+            .map_err(|_| "Failed to open response stream".to_string())?;
+        write_all(&stream, body).map_err(|e| format!("Failed to write response: {:?}", e))?;
+    }
+
+    OutgoingBody::finish(response_body, None).map_err(|e| format!("Failed to finish response: {:?}", e))
+}
+
+// Header errors mean an invalid name or value; that header is dropped
+// rather than failing the response
+fn set_header(headers: &Fields, name: &str, value: &[u8]) {
+    if let Err(e) = headers.set(&name.to_string(), &[value.to_vec()]) {
+        log(Level::Warn, "recipe-api", &format!("Dropping header {}: {:?}", name, e));
+    }
+}
+
+fn append_header(headers: &Fields, name: &str, value: &[u8]) {
+    if let Err(e) = headers.append(&name.to_string(), &value.to_vec()) {
+        log(Level::Warn, "recipe-api", &format!("Dropping header {}: {:?}", name, e));
+    }
+}
+
+// Copies `body` into the response as it's read, so large objects aren't held
+// in memory. The response goes to the host before the copy starts so the
+// client can begin receiving straight away.
+fn send_stream_response(status: u16, headers: Fields, body: InputStream, response_out: ResponseOutparam) {
+    let Some(response_body) = begin_stream_response(status, headers, response_out) else {
+        return;
+    };
+
+    match response_body.write() {
+        Ok(stream) => {
+            while let Ok(chunk) = body.blocking_read(4096) {
+                // A failed write means the client went away
+                if chunk.is_empty() || stream.blocking_write_and_flush(&chunk).is_err() {
+                    break;
+                }
+            }
+        }
+        Err(_) => log(Level::Error, "recipe-api", "Failed to open response stream"),
+    }
+
+    finish_stream_response(response_body);
+}
+
+// Sends the status and headers now and returns the body for the caller to
+// write, so output reaches the client as it's produced. Finish the body
+// with finish_stream_response. None means the response has already been
+// failed and there is nothing left to do.
+fn begin_stream_response(status: u16, headers: Fields, response_out: ResponseOutparam) -> Option<OutgoingBody> {
+    let response = OutgoingResponse::new(headers);
+    let status = set_status(&response, status);
+
+    match response.body() {
+        Ok(body) => {
+            REQUEST_CONTEXT.with(|ctx| ctx.borrow_mut().status = Some(status));
+            ResponseOutparam::set(response_out, Ok(response));
+            Some(body)
+        }
+        Err(_) => {
+            log(Level::Error, "recipe-api", "Failed to get response body");
+            REQUEST_CONTEXT.with(|ctx| ctx.borrow_mut().status = Some(500));
+            ResponseOutparam::set(response_out, Err(ErrorCode::InternalError(Some("Failed to get response body".to_string()))));
+            None
+        }
+    }
+}
+
+// The status has already been sent, so a failure here can only be logged
+fn finish_stream_response(body: OutgoingBody) {
+    if let Err(e) = OutgoingBody::finish(body, None) {
+        log(Level::Warn, "recipe-api", &format!("Failed to finish streamed response: {:?}", e));
+    }
+}
+
+// blocking_write_and_flush takes at most 4096 bytes per call
+fn write_all(stream: &OutputStream, bytes: &[u8]) -> Result<(), wasi::io::streams::StreamError> {
+    for chunk in bytes.chunks(4096) {
+        stream.blocking_write_and_flush(chunk)?;
+    }
+    Ok(())
+}
+
+// Headers sent on every response, including CORS so browsers can call the API
+fn response_headers() -> Fields {
+    let headers = Fields::new();
+    let origin = cors_allowed_origin();
+
+    if origin != "*" {
+        set_header(&headers, "vary", b"Origin");
+    }
+    set_header(&headers, "access-control-allow-origin", origin.as_bytes());
+    set_header(&headers, "access-control-allow-methods", CORS_ALLOWED_METHODS.as_bytes());
+    set_header(&headers, "access-control-allow-headers", CORS_ALLOWED_HEADERS.as_bytes());
+    set_header(&headers, "access-control-expose-headers", CORS_EXPOSED_HEADERS.as_bytes());
+
+    let request_id = REQUEST_CONTEXT.with(|ctx| ctx.borrow().request_id.clone());
+    set_header(&headers, "x-request-id", request_id.as_bytes());
+
+    headers
+}
+
+// Allowed origin comes from the `cors_allowed_origin` config key, defaulting to any
+fn cors_allowed_origin() -> String {
+    config_value("cors_allowed_origin").unwrap_or_else(|| "*".to_string())
+}
+
+// Every keyvalue access goes through here, so one deployment's data can be
+// kept apart from another's by setting `bucket_name`
+fn open_bucket() -> Result<Bucket, RecipeError> {
+    open(&bucket_name()).map_err(|e| RecipeError::Storage(format!("Failed to open bucket: {:?}", e)))
+}
+
+fn bucket_name() -> String {
+    BUCKET_NAME.with(|name| {
+        name.get_or_init(|| {
+            config_value("bucket_name")
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| DEFAULT_BUCKET_NAME.to_string())
+        })
+        .clone()
+    })
+}
+
+fn config_value(key: &str) -> Option<String> {
+    wasi::config::runtime::get(key).ok().flatten()
+}
+
+enum AdminAccess {
+    Granted,
+    // No `admin_token` is configured, so admin endpoints are off
+    Disabled,
+    Denied,
+}
+
+// Admin endpoints require the `X-Admin-Token` header to equal the
+// `admin_token` config key
+fn admin_access(headers: &Fields) -> AdminAccess {
+    let Some(expected) = config_value("admin_token").filter(|token| !token.is_empty()) else {
+        return AdminAccess::Disabled;
+    };
+
+    match header_value(headers, "x-admin-token") {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => AdminAccess::Granted,
+        _ => AdminAccess::Denied,
+    }
+}
+
+// Compares without stopping at the first difference, so response timing
+// doesn't reveal how much of a guessed token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// Unparseable values fall back to the default rather than failing requests
+fn config_u64(key: &str, default: u64) -> u64 {
+    config_value(key).and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+}
+
+// Upper bound on prep and cook time, from the `max_time_mins` config key
+fn max_time_mins() -> u64 {
+    config_u64("max_time_mins", DEFAULT_MAX_TIME_MINS)
+}
+
+// Token-bucket limit per client: up to `rate_limit_requests` in a burst,
+// refilled at that many per `rate_limit_window_secs`. Returns the seconds
+// until a token is available if the client is out of them. Requests without
+// a client ID aren't limited, and storage errors let the request through.
+fn check_rate_limit(headers: &Fields) -> Option<u64> {
+    let header = config_value("rate_limit_header").unwrap_or_else(|| DEFAULT_RATE_LIMIT_HEADER.to_string());
+    let client = header_value(headers, &header.to_lowercase()).filter(|id| is_safe_identifier(id))?;
+
+    let limit = config_u64("rate_limit_requests", DEFAULT_RATE_LIMIT_REQUESTS);
+    let window_nanos = config_u64("rate_limit_window_secs", DEFAULT_RATE_LIMIT_WINDOW_SECS).max(1) * 1_000_000_000;
+
+    match take_token(&client, limit, window_nanos) {
+        Ok(retry_after) => retry_after,
+        Err(e) => {
+            log(Level::Warn, "recipe-api", &format!("Rate limit check failed: {}", e));
+            None
+        }
+    }
+}
+
+// A client's bucket, stored at `ratelimit:{client}`
+#[derive(Serialize, Deserialize)]
+struct TokenBucket {
+    tokens: f64,
+    // Nanosecond timestamp `tokens` was last brought up to date
+    refilled_at: u64,
+}
+
+impl TokenBucket {
+    // Tops up for the time since the last refill, then takes a token if one
+    // is there. Returns the seconds until the next token otherwise. A client
+    // seen for the first time starts with a full bucket.
+    fn take(state: Option<TokenBucket>, now: u64, limit: u64, window_nanos: u64) -> (TokenBucket, Option<u64>) {
+        let capacity = limit as f64;
+        let per_nano = capacity / window_nanos as f64;
+        let tokens = match state {
+            Some(state) => {
+                let elapsed = now.saturating_sub(state.refilled_at);
+                (state.tokens + elapsed as f64 * per_nano).min(capacity)
+            }
+            None => capacity,
+        };
+
+        if tokens >= 1.0 {
+            return (TokenBucket { tokens: tokens - 1.0, refilled_at: now }, None);
+        }
+
+        let retry_after = if limit == 0 {
+            window_nanos.div_ceil(1_000_000_000)
+        } else {
+            let wait_nanos = ((1.0 - tokens) / per_nano).ceil() as u64;
+            wait_nanos.div_ceil(1_000_000_000).max(1)
+        };
+        (TokenBucket { tokens, refilled_at: now }, Some(retry_after))
+    }
+}
+
+// Reads, refills and writes back the client's bucket. keyvalue has no
+// compare-and-set, so requests racing on one client can both spend the same
+// token; the limit can be overshot by the number of concurrent requests,
+// never by more.
+fn take_token(client: &str, limit: u64, window_nanos: u64) -> Result<Option<u64>, RecipeError> {
+    let bucket = open_bucket()?;
+    let key = format!("ratelimit:{}", client);
+
+    let state = bucket
+        .get(&key)
+        .map_err(|e| RecipeError::Storage(format!("Failed to read rate limit: {:?}", e)))?
+        .and_then(|data| serde_json::from_slice::<TokenBucket>(&data).ok());
+    let (state, retry_after) = TokenBucket::take(state, current_timestamp_nanos(), limit, window_nanos);
+
+    let data = serde_json::to_vec(&state).map_err(|e| RecipeError::Serialization(format!("Failed to serialize: {:?}", e)))?;
+    bucket
+        .set(&key, &data)
+        .map_err(|e| RecipeError::Storage(format!("Failed to store rate limit: {:?}", e)))?;
+
+    Ok(retry_after)
+}
+
+fn method_name(method: &Method) -> String {
+    match method {
+        Method::Get => "GET",
+        Method::Head => "HEAD",
+        Method::Post => "POST",
+        Method::Put => "PUT",
+        Method::Delete => "DELETE",
+        Method::Connect => "CONNECT",
+        Method::Options => "OPTIONS",
+        Method::Trace => "TRACE",
+        Method::Patch => "PATCH",
+        Method::Other(other) => other,
+    }
+    .to_string()
+}
+
+// Counters live at `metrics:requests:{METHOD}` and `metrics:errors:{status}`
+// and are bumped with atomic increments, so concurrent requests never lose
+// a count. Nonstandard methods share one OTHER counter to keep the key
+// space bounded. Each error status is also added to METRICS_STATUS_INDEX,
+// which is a no-op once it's there.
+fn record_request_metrics(kv: &dyn BucketOps, method: &str, status: Option<u16>) -> Result<(), RecipeError> {
+    let method = if CORS_ALLOWED_METHODS.split(", ").any(|known| known == method) { method } else { "OTHER" };
+    kv.increment(&format!("{}{}", METRICS_REQUESTS_PREFIX, method), 1)?;
+
+    if let Some(status) = status.filter(|&status| status >= 400) {
+        kv.increment(&format!("{}{}", METRICS_ERRORS_PREFIX, status), 1)?;
+        add_to_index(kv, METRICS_STATUS_INDEX, &status.to_string())?;
+    }
+
+    Ok(())
+}
+
+// Reads the counters from keys it can name without listing the bucket: one
+// per known method plus OTHER, and one per status in METRICS_STATUS_INDEX.
+// A counter that can't be read is left out rather than failing the scrape.
+fn render_metrics(kv: &dyn BucketOps) -> Result<String, RecipeError> {
+    let mut methods: Vec<&str> = CORS_ALLOWED_METHODS.split(", ").chain(["OTHER"]).collect();
+    methods.sort_unstable();
+    let mut statuses: Vec<u16> = load_index(kv, METRICS_STATUS_INDEX)?
+        .iter()
+        .filter_map(|status| status.parse().ok())
+        .collect();
+    statuses.sort_unstable();
+
+    let mut keys: Vec<String> = methods.iter().map(|method| format!("{}{}", METRICS_REQUESTS_PREFIX, method)).collect();
+    keys.extend(statuses.iter().map(|status| format!("{}{}", METRICS_ERRORS_PREFIX, status)));
+
+    let mut requests = String::new();
+    let mut errors = String::new();
+    for key in &keys {
+        let Some(value) = read_counter(kv, key) else {
+            continue;
+        };
+        if let Some(method) = key.strip_prefix(METRICS_REQUESTS_PREFIX) {
+            requests.push_str(&format!("recipe_api_requests_total{{method=\"{}\"}} {}\n", method, value));
+        } else if let Some(status) = key.strip_prefix(METRICS_ERRORS_PREFIX) {
+            errors.push_str(&format!("recipe_api_errors_total{{status=\"{}\"}} {}\n", status, value));
+        }
+    }
+
+    Ok(format!(
+        "# HELP recipe_api_requests_total Requests handled, by method.\n\
+         # TYPE recipe_api_requests_total counter\n\
+         {}\
+         # HELP recipe_api_errors_total Responses with a 4xx or 5xx status, by status.\n\
+         # TYPE recipe_api_errors_total counter\n\
+         {}",
+        requests, errors
+    ))
+}
+
+// A counter's value, or None if it was never set or can't be read
+fn read_counter(kv: &dyn BucketOps, key: &str) -> Option<u64> {
+    let read = match kv.exists(key) {
+        Ok(true) => kv.increment(key, 0).map(Some),
+        Ok(false) => Ok(None),
+        Err(e) => Err(e),
+    };
+    read.unwrap_or_else(|e| {
+        log(Level::Warn, "recipe-api", &format!("Failed to read counter {}: {}", key, e));
+        None
+    })
+}
+
+// Wraps wasi logging so every line carries the current request's ID
+fn log(level: Level, context: &str, message: &str) {
+    let request_id = REQUEST_CONTEXT.with(|ctx| ctx.borrow().request_id.clone());
+    wasi::logging::logging::log(level, context, &format!("[{}] {}", request_id, message));
+}
+
+fn generate_request_id() -> String {
+    format!("{:016x}", wasi::random::random::get_random_u64())
+}
+
+// Printable ASCII without spaces, so a client can't forge log lines or keys
+fn is_safe_identifier(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_IDENTIFIER_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+fn current_timestamp() -> u64 {
+    use wasi::clocks::wall_clock::now;
+    let duration = now();
     duration.seconds
 }
+
+fn current_timestamp_nanos() -> u64 {
+    use wasi::clocks::wall_clock::now;
+    let duration = now();
+    duration.seconds * 1_000_000_000 + duration.nanoseconds as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe(name: &str) -> RecipeJson {
+        RecipeJson {
+            id: String::new(),
+            name: name.to_string(),
+            description: None,
+            ingredients: vec![ingredient("flour", 200.0, "g")],
+            instructions: vec![step(1, "Mix")],
+            servings: 2,
+            prep_time_mins: 10,
+            cook_time_mins: 20,
+            difficulty: "easy".to_string(),
+            tags: Vec::new(),
+            dietary_info: Vec::new(),
+            author: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn ingredient(name: &str, amount: f32, unit: &str) -> IngredientJson {
+        IngredientJson {
+            name: name.to_string(),
+            amount,
+            unit: unit.to_string(),
+            optional: false,
+            notes: None,
+            calories_per_unit: None,
+        }
+    }
+
+    fn step(order: u8, instruction: &str) -> StepJson {
+        StepJson { order, instruction: instruction.to_string(), duration_mins: None }
+    }
+
+    fn problems(recipe: &RecipeJson) -> Vec<String> {
+        validate(recipe, DEFAULT_MAX_TIME_MINS).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn a_complete_recipe_is_valid() {
+        assert!(problems(&recipe("Pancakes")).is_empty());
+    }
+
+    #[test]
+    fn name_must_not_be_blank() {
+        assert_eq!(problems(&recipe("   ")), ["name must not be empty"]);
+    }
+
+    #[test]
+    fn servings_must_be_at_least_one() {
+        let mut pancakes = recipe("Pancakes");
+        pancakes.servings = 0;
+        assert_eq!(problems(&pancakes), ["servings must be at least 1"]);
+    }
+
+    #[test]
+    fn difficulty_must_be_a_known_level() {
+        let mut pancakes = recipe("Pancakes");
+        pancakes.difficulty = "expert".to_string();
+        assert_eq!(problems(&pancakes), ["difficulty must be one of: easy, medium, hard (got 'expert')"]);
+
+        // Levels are matched exactly, as stored
+        pancakes.difficulty = "Easy".to_string();
+        assert_eq!(problems(&pancakes).len(), 1);
+    }
+
+    #[test]
+    fn ingredient_amounts_must_not_be_negative() {
+        let mut pancakes = recipe("Pancakes");
+        pancakes.ingredients.push(ingredient("milk", -1.0, "ml"));
+        pancakes.ingredients.push(ingredient("salt", 0.0, "pinch"));
+        assert_eq!(problems(&pancakes), ["ingredient 'milk' has a negative amount"]);
+    }
+
+    #[test]
+    fn step_orders_must_be_unique() {
+        let mut pancakes = recipe("Pancakes");
+        pancakes.instructions = vec![step(1, "Mix"), step(2, "Rest"), step(2, "Fry"), step(2, "Serve")];
+        assert_eq!(problems(&pancakes), ["step order 2 is used more than once", "step order 2 is used more than once"]);
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let mut pancakes = recipe("");
+        pancakes.servings = 0;
+        pancakes.difficulty = String::new();
+        assert_eq!(problems(&pancakes).len(), 3);
+    }
+
+    #[test]
+    fn explicit_ids_must_be_path_safe() {
+        let mut pancakes = recipe("Pancakes");
+        pancakes.id = "weekend-pancakes_2".to_string();
+        assert!(problems(&pancakes).is_empty());
+
+        for id in ["search", "a/b", "a:v1", "with space"] {
+            pancakes.id = id.to_string();
+            assert_eq!(problems(&pancakes).len(), 1, "{}", id);
+        }
+
+        pancakes.id = "x".repeat(MAX_IDENTIFIER_LEN + 1);
+        assert_eq!(problems(&pancakes), ["id must be at most 128 characters"]);
+    }
+
+    #[test]
+    fn times_up_to_the_bound_are_accepted() {
+        let mut soup = recipe("Soup");
+        soup.prep_time_mins = 0;
+        soup.cook_time_mins = 1440;
+        assert!(validate(&soup, 1440).is_ok());
+    }
+
+    #[test]
+    fn times_past_the_bound_are_rejected() {
+        let mut soup = recipe("Soup");
+        soup.prep_time_mins = 1441;
+        soup.cook_time_mins = 999_999;
+        let problems = validate(&soup, 1440).unwrap_err();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("prep_time_mins must be at most 1440"));
+        assert!(problems[1].starts_with("cook_time_mins must be at most 1440"));
+    }
+
+    #[test]
+    fn time_bound_follows_the_configured_max() {
+        let mut stew = recipe("Stew");
+        stew.cook_time_mins = 2000;
+        assert!(validate(&stew, 1440).is_err());
+        assert!(validate(&stew, 2880).is_ok());
+    }
+
+    #[test]
+    fn gzipped_body_round_trips() {
+        use std::io::Read;
+
+        let page = serde_json::json!({ "data": vec![recipe("Pancakes"); 20], "total": 20 });
+        let json = page.to_string();
+        assert!(json.len() >= GZIP_MIN_BYTES);
+
+        let compressed = gzip(json.as_bytes()).unwrap();
+        assert!(compressed.len() < json.len());
+
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(compressed.as_slice()).read_to_string(&mut decompressed).unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&decompressed).unwrap(), page);
+    }
+
+    #[test]
+    fn gzip_is_only_used_when_accepted() {
+        assert!(accepts_gzip("gzip"));
+        assert!(accepts_gzip("deflate, GZIP;q=0.5"));
+        assert!(!accepts_gzip("deflate, br"));
+        assert!(!accepts_gzip("gzip;q=0"));
+        assert!(!accepts_gzip("x-gzip"));
+    }
+
+    fn sorted_names(mut recipes: Vec<RecipeJson>, query_string: &str) -> Vec<String> {
+        let sort = SortOrder::from_query(&Query::parse(query_string)).unwrap();
+        sort_recipes(&mut recipes, &sort);
+        recipes.into_iter().map(|recipe| recipe.name).collect()
+    }
+
+    fn graded(name: &str, difficulty: &str, created_at: u64) -> RecipeJson {
+        RecipeJson { difficulty: difficulty.to_string(), created_at, ..recipe(name) }
+    }
+
+    #[test]
+    fn later_sort_keys_break_ties() {
+        let recipes = vec![
+            graded("Risotto", "hard", 1),
+            graded("toast", "easy", 2),
+            graded("Omelette", "medium", 3),
+            graded("Avocado toast", "easy", 4),
+            graded("Bread", "hard", 5),
+        ];
+        assert_eq!(sorted_names(recipes, "sort=difficulty,name"), ["Avocado toast", "toast", "Omelette", "Bread", "Risotto"]);
+    }
+
+    #[test]
+    fn difficulty_sorts_by_level_not_alphabetically() {
+        let recipes = vec![graded("A", "medium", 1), graded("B", "hard", 2), graded("C", "easy", 3), graded("D", "unknown", 4)];
+        assert_eq!(sorted_names(recipes.clone(), "sort=difficulty"), ["C", "A", "B", "D"]);
+        assert_eq!(sorted_names(recipes, "sort=-difficulty"), ["D", "B", "A", "C"]);
+    }
+
+    #[test]
+    fn each_key_has_its_own_direction() {
+        let recipes = vec![graded("Soup", "easy", 1), graded("Cake", "easy", 2), graded("Salad", "easy", 2)];
+        assert_eq!(sorted_names(recipes.clone(), "sort=-created_at,name"), ["Cake", "Salad", "Soup"]);
+        // `order=desc` applies to keys without their own prefix
+        assert_eq!(sorted_names(recipes, "sort=created_at,-name&order=desc"), ["Salad", "Cake", "Soup"]);
+    }
+
+    #[test]
+    fn unknown_sort_keys_are_skipped() {
+        let recipes = vec![graded("b", "easy", 1), graded("a", "easy", 2)];
+        assert_eq!(sorted_names(recipes, "sort=rating,name"), ["a", "b"]);
+        assert!(SortOrder::from_query(&Query::parse("sort=rating")).is_none());
+    }
+
+    #[test]
+    fn full_ties_keep_listing_order() {
+        let recipes = vec![graded("First", "easy", 1), graded("Second", "easy", 1), graded("Third", "easy", 1)];
+        assert_eq!(sorted_names(recipes.clone(), "sort=created_at"), ["First", "Second", "Third"]);
+        assert_eq!(sorted_names(recipes, "sort=-created_at"), ["First", "Second", "Third"]);
+    }
+
+    // Bucket stand-in for the index tests. `interleave` runs another
+    // writer's code just before a chosen increment, like a concurrent request
+    // landing between this writer's read and its write.
+    #[derive(Default)]
+    struct MemoryBucket {
+        values: RefCell<BTreeMap<String, Vec<u8>>>,
+        counters: RefCell<BTreeMap<String, u64>>,
+        interleave: RefCell<Option<(String, Writer)>>,
+    }
+
+    type Writer = Box<dyn FnOnce(&MemoryBucket)>;
+
+    impl BucketOps for MemoryBucket {
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, RecipeError> {
+            Ok(self.values.borrow().get(key).cloned())
+        }
+
+        fn set(&self, key: &str, value: &[u8]) -> Result<(), RecipeError> {
+            self.values.borrow_mut().insert(key.to_string(), value.to_vec());
+            Ok(())
+        }
+
+        fn delete(&self, key: &str) -> Result<(), RecipeError> {
+            self.values.borrow_mut().remove(key);
+            self.counters.borrow_mut().remove(key);
+            Ok(())
+        }
+
+        fn exists(&self, key: &str) -> Result<bool, RecipeError> {
+            Ok(self.values.borrow().contains_key(key) || self.counters.borrow().contains_key(key))
+        }
+
+        fn increment(&self, key: &str, delta: u64) -> Result<u64, RecipeError> {
+            let due = matches!(&*self.interleave.borrow(), Some((before, _)) if before == key);
+            if due {
+                if let Some((_, other_writer)) = self.interleave.take() {
+                    other_writer(self);
+                }
+            }
+
+            let mut counters = self.counters.borrow_mut();
+            let value = counters.entry(key.to_string()).or_insert(0);
+            *value += delta;
+            Ok(*value)
+        }
+    }
+
+    impl MemoryBucket {
+        fn interleave_before(&self, key: &str, other_writer: impl FnOnce(&MemoryBucket) + 'static) {
+            *self.interleave.borrow_mut() = Some((key.to_string(), Box::new(other_writer)));
+        }
+    }
+
+    // RecipeStore held in memory, so handler logic runs without a host.
+    // Writes skip prepare_recipe, and no versions are kept.
+    #[derive(Default)]
+    struct MemoryStore {
+        recipes: RefCell<BTreeMap<String, RecipeJson>>,
+        deleted: RefCell<BTreeMap<String, RecipeJson>>,
+        favorites: RefCell<Vec<String>>,
+        views: RefCell<BTreeMap<String, u64>>,
+        ratings: RefCell<BTreeMap<String, (u64, u64)>>,
+    }
+
+    impl RecipeStore for MemoryStore {
+        fn get(&self, id: &str) -> Result<Option<RecipeJson>, RecipeError> {
+            Ok(self.recipes.borrow().get(id).cloned())
+        }
+
+        fn list(&self) -> Result<Vec<String>, RecipeError> {
+            Ok(self.recipes.borrow().keys().cloned().collect())
+        }
+
+        fn create(&self, mut recipe: RecipeJson) -> Result<String, RecipeError> {
+            if recipe.id.is_empty() {
+                recipe.id = ids::slugify(&recipe.name);
+            }
+            if self.exists(&recipe.id)? {
+                return Err(RecipeError::Conflict);
+            }
+            let id = recipe.id.clone();
+            self.recipes.borrow_mut().insert(id.clone(), recipe);
+            Ok(id)
+        }
+
+        fn update(&self, id: &str, mut recipe: RecipeJson) -> Result<RecipeJson, RecipeError> {
+            recipe.id = id.to_string();
+            self.recipes.borrow_mut().insert(id.to_string(), recipe.clone());
+            Ok(recipe)
+        }
+
+        fn delete(&self, id: &str, hard: bool) -> Result<(), RecipeError> {
+            let removed = self.recipes.borrow_mut().remove(id);
+            if hard {
+                self.deleted.borrow_mut().remove(id);
+                self.views.borrow_mut().remove(id);
+                self.ratings.borrow_mut().remove(id);
+            } else if let Some(recipe) = removed {
+                self.deleted.borrow_mut().insert(id.to_string(), recipe);
+            }
+            self.favorites.borrow_mut().retain(|favorite| favorite != id);
+            Ok(())
+        }
+
+        fn exists(&self, id: &str) -> Result<bool, RecipeError> {
+            Ok(self.recipes.borrow().contains_key(id))
+        }
+
+        fn restore(&self, id: &str) -> Result<RestoreOutcome, RecipeError> {
+            if !self.deleted.borrow().contains_key(id) {
+                return Ok(RestoreOutcome::NotDeleted);
+            }
+            if self.exists(id)? {
+                return Ok(RestoreOutcome::Conflict);
+            }
+            let recipe = self.deleted.borrow_mut().remove(id).unwrap();
+            self.recipes.borrow_mut().insert(id.to_string(), recipe.clone());
+            Ok(RestoreOutcome::Restored(Box::new(recipe)))
+        }
+
+        fn deleted(&self) -> Result<Vec<RecipeJson>, RecipeError> {
+            Ok(self.deleted.borrow().values().cloned().collect())
+        }
+
+        fn favorites(&self) -> Result<Vec<String>, RecipeError> {
+            Ok(self.favorites.borrow().clone())
+        }
+
+        fn set_favorite(&self, id: &str, favorite: bool) -> Result<(), RecipeError> {
+            if !self.exists(id)? {
+                return Err(RecipeError::NotFound);
+            }
+            let mut favorites = self.favorites.borrow_mut();
+            favorites.retain(|existing| existing != id);
+            if favorite {
+                favorites.push(id.to_string());
+            }
+            Ok(())
+        }
+
+        fn versions(&self, _id: &str) -> Result<Vec<VersionSummary>, RecipeError> {
+            Ok(Vec::new())
+        }
+
+        fn version(&self, _id: &str, _version: u64) -> Result<Option<RecipeJson>, RecipeError> {
+            Ok(None)
+        }
+
+        fn record_view(&self, id: &str) -> Result<(), RecipeError> {
+            *self.views.borrow_mut().entry(id.to_string()).or_insert(0) += 1;
+            Ok(())
+        }
+
+        fn views(&self, id: &str) -> Result<u64, RecipeError> {
+            Ok(self.views.borrow().get(id).copied().unwrap_or(0))
+        }
+
+        fn rate(&self, id: &str, rating: u64) -> Result<RatingSummary, RecipeError> {
+            let mut ratings = self.ratings.borrow_mut();
+            let (sum, count) = ratings.entry(id.to_string()).or_insert((0, 0));
+            *sum += rating;
+            *count += 1;
+            Ok(RatingSummary::from_totals(*sum, *count, 0))
+        }
+
+        fn rating(&self, id: &str) -> Result<RatingSummary, RecipeError> {
+            let (sum, count) = self.ratings.borrow().get(id).copied().unwrap_or((0, 0));
+            Ok(RatingSummary::from_totals(sum, count, 0))
+        }
+
+        fn ping(&self) -> Result<(), RecipeError> {
+            Ok(())
+        }
+    }
+
+    // A store holding one recipe per name, each under its slug
+    fn store_with(names: &[&str]) -> MemoryStore {
+        let store = MemoryStore::default();
+        for name in names {
+            store.create(recipe(name)).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn interleaved_creates_are_both_listed() {
+        let bucket = MemoryBucket::default();
+        // The second create allocates its slot after the first has checked the index
+        bucket.interleave_before("_recipe_ids:next", |bucket| add_to_index(bucket, RECIPE_INDEX, "soup").unwrap());
+        add_to_index(&bucket, RECIPE_INDEX, "salad").unwrap();
+
+        assert_eq!(load_index(&bucket, RECIPE_INDEX).unwrap(), ["soup", "salad"]);
+    }
+
+    #[test]
+    fn interleaved_adds_of_one_id_take_one_slot() {
+        let bucket = MemoryBucket::default();
+        // Both writers have seen the ID missing from the index
+        bucket.interleave_before("_recipe_ids:claim:soup", |bucket| add_to_index(bucket, RECIPE_INDEX, "soup").unwrap());
+        add_to_index(&bucket, RECIPE_INDEX, "soup").unwrap();
+
+        assert_eq!(load_index_slots(&bucket, RECIPE_INDEX).unwrap(), [(1, "soup".to_string())]);
+
+        remove_from_index(&bucket, RECIPE_INDEX, "soup").unwrap();
+        assert!(load_index(&bucket, RECIPE_INDEX).unwrap().is_empty());
+
+        // Once removed, the ID can be added again
+        add_to_index(&bucket, RECIPE_INDEX, "soup").unwrap();
+        assert_eq!(load_index(&bucket, RECIPE_INDEX).unwrap(), ["soup"]);
+    }
+
+    #[test]
+    fn batched_adds_keep_their_order() {
+        let bucket = MemoryBucket::default();
+        let ids: Vec<String> = ["a", "b", "a", "c"].iter().map(|id| id.to_string()).collect();
+        add_many_to_index(&bucket, RECIPE_INDEX, &ids).unwrap();
+        add_many_to_index(&bucket, RECIPE_INDEX, &ids[1..]).unwrap();
+
+        assert_eq!(load_index(&bucket, RECIPE_INDEX).unwrap(), ["a", "b", "c"]);
+        assert_eq!(bucket.increment("_recipe_ids:next", 0).unwrap(), 3);
+    }
+
+    #[test]
+    fn duplicate_slots_from_before_claims_are_freed() {
+        // Two unclaimed slots for "soup", the marker pointing at the second
+        let bucket = MemoryBucket::default();
+        bucket.increment("_recipe_ids:next", 3).unwrap();
+        for (slot, id) in [(1, "soup"), (2, "salad"), (3, "soup")] {
+            bucket.set(&format!("_recipe_ids:{}", slot), id.as_bytes()).unwrap();
+            bucket.set(&format!("_recipe_ids:id:{}", id), slot.to_string().as_bytes()).unwrap();
+        }
+        assert_eq!(load_index(&bucket, RECIPE_INDEX).unwrap(), ["soup", "salad"]);
+
+        let slots = load_index_slots(&bucket, RECIPE_INDEX).unwrap();
+        free_duplicate_slots(&bucket, RECIPE_INDEX, &slots).unwrap();
+        assert_eq!(load_index_slots(&bucket, RECIPE_INDEX).unwrap(), [(2, "salad".to_string()), (3, "soup".to_string())]);
+
+        remove_from_index(&bucket, RECIPE_INDEX, "soup").unwrap();
+        assert_eq!(load_index(&bucket, RECIPE_INDEX).unwrap(), ["salad"]);
+    }
+
+    #[test]
+    fn second_create_with_an_explicit_id_conflicts() {
+        let bucket = MemoryBucket::default();
+        assert!(ensure_id_free(&bucket, "soup").is_ok());
+        bucket.set("recipe:soup", b"{}").unwrap();
+
+        let conflict = ensure_id_free(&bucket, "soup").unwrap_err();
+        assert!(matches!(conflict, RecipeError::Conflict));
+        assert_eq!(conflict.status(), 409);
+        // Only that exact ID is taken
+        assert!(ensure_id_free(&bucket, "salad").is_ok());
+        assert!(ensure_id_free(&bucket, "sou").is_ok());
+    }
+
+    #[test]
+    fn bulk_items_report_conflicts_as_409() {
+        let results = [
+            BulkResult::succeeded(0, 201, "soup".to_string()),
+            BulkResult::from_error(1, Some("soup".to_string()), RecipeError::Conflict),
+        ];
+        assert_eq!(results[1].status, 409);
+        assert_eq!(results[1].id.as_deref(), Some("soup"));
+        assert_eq!(multi_status(&results, 201), 207);
+    }
+
+    fn search_terms(query: &str) -> Vec<String> {
+        query.split_whitespace().map(plural::normalize).collect()
+    }
+
+    #[test]
+    fn fuzzy_search_tolerates_typos() {
+        let soup = recipe("Chicken Soup");
+        let score = score_recipe(&soup, &search_terms("chiken"), true).unwrap();
+        assert_eq!(score.matched_field, "name");
+        assert_eq!(score.fuzzy_terms, 1);
+        assert_eq!(score.distance, 1);
+
+        assert!(score_recipe(&soup, &search_terms("chiken"), false).is_none());
+        assert!(score_recipe(&soup, &search_terms("chkn"), true).is_none());
+    }
+
+    #[test]
+    fn exact_matches_rank_above_fuzzy_ones() {
+        let terms = search_terms("chicken");
+        let exact = score_recipe(&recipe("Chicken Soup"), &terms, true).unwrap();
+        let substring = score_recipe(&recipe("Chickenless Pie"), &terms, true).unwrap();
+        let fuzzy = score_recipe(&recipe("Chickem Curry"), &terms, true).unwrap();
+        assert_eq!(exact.fuzzy_terms, 0);
+        assert_eq!(substring.fuzzy_terms, 0);
+        assert_eq!(fuzzy.fuzzy_terms, 1);
+
+        let mut ranked = [("fuzzy", &fuzzy), ("substring", &substring), ("exact", &exact)];
+        ranked.sort_by_key(|(_, score)| score.rank());
+        // Equal scores keep their order; the fuzzy-only match sinks
+        let names: Vec<&str> = ranked.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["substring", "exact", "fuzzy"]);
+    }
+
+    #[test]
+    fn labels_become_kebab_case() {
+        assert_eq!(kebab_case("Quick Dinner"), "quick-dinner");
+        assert_eq!(kebab_case("  Quick   Weeknight\tDinner "), "quick-weeknight-dinner");
+        assert_eq!(kebab_case("GLUTEN_free"), "gluten-free");
+        assert_eq!(kebab_case("one--pot"), "one-pot");
+        assert_eq!(kebab_case(" - "), "");
+    }
+
+    #[test]
+    fn normalized_labels_drop_duplicates_and_blanks() {
+        let labels: Vec<String> = ["Quick Dinner", "vegan", "quick  dinner", "  ", "QUICK-DINNER", "Vegan"]
+            .iter()
+            .map(|label| label.to_string())
+            .collect();
+        assert_eq!(normalize_labels(&labels), ["quick-dinner", "vegan"]);
+    }
+
+    #[test]
+    fn paths_collapse_repeated_and_trailing_slashes() {
+        assert_eq!(normalize_path("/api/recipes"), "/api/recipes");
+        assert_eq!(normalize_path("/api/recipes/"), "/api/recipes");
+        assert_eq!(normalize_path("//api///recipes//soup/"), "/api/recipes/soup");
+        assert_eq!(normalize_path("api/recipes"), "/api/recipes");
+        assert_eq!(normalize_path(""), "/");
+        assert_eq!(normalize_path("///"), "/");
+    }
+
+    #[test]
+    fn fetched_recipes_can_be_sent_back() {
+        let mut soup = recipe("Soup");
+        soup.id = "soup".to_string();
+        let view = RecipeView::from(soup)
+            .with_links()
+            .with_notes()
+            .with_rating(RatingSummary::from_totals(9, 2, 0));
+        let body = serde_json::to_vec(&view).unwrap();
+        let fields: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        for field in ["total_time_mins", "_links", "all_notes", "average_rating", "rating_count"] {
+            assert!(fields.get(field).is_some(), "{} missing from the view", field);
+        }
+        // RecipeJson itself stays strict
+        assert!(serde_json::from_slice::<RecipeJson>(&body).is_err());
+
+        let parsed = parse_recipe(&body).unwrap();
+        assert_eq!(parsed.id, "soup");
+        assert!(recipe_from_value(fields).is_ok());
+    }
+
+    #[test]
+    fn other_unknown_recipe_fields_are_rejected() {
+        let mut fields = serde_json::to_value(recipe("Soup")).unwrap();
+        fields["serving"] = serde_json::json!(4);
+        let body = serde_json::to_vec_pretty(&fields).unwrap();
+        let error = parse_recipe(&body).err().unwrap();
+        assert!(error.to_string().contains("unknown field `serving`"));
+        // Still points at where parsing stopped
+        assert!(error.line() > 1);
+
+        fields["total_time_mins"] = serde_json::json!(30);
+        let error = recipe_from_value(fields).err().unwrap();
+        assert!(error.to_string().contains("unknown field `serving`"));
+    }
+
+    const MINUTE: u64 = 60_000_000_000;
+
+    #[test]
+    fn token_bucket_allows_a_full_burst_then_limits() {
+        let (mut state, retry_after) = TokenBucket::take(None, 0, 3, MINUTE);
+        assert_eq!(retry_after, None);
+        for _ in 0..2 {
+            let (next, retry_after) = TokenBucket::take(Some(state), 0, 3, MINUTE);
+            assert_eq!(retry_after, None);
+            state = next;
+        }
+
+        // Out of tokens; one refills every 20 seconds
+        let (state, retry_after) = TokenBucket::take(Some(state), 0, 3, MINUTE);
+        assert_eq!(retry_after, Some(20));
+        let (_, retry_after) = TokenBucket::take(Some(state), 5_000_000_000, 3, MINUTE);
+        assert_eq!(retry_after, Some(15));
+    }
+
+    #[test]
+    fn token_bucket_refills_over_time_up_to_the_limit() {
+        let empty = TokenBucket { tokens: 0.0, refilled_at: 0 };
+        let (state, retry_after) = TokenBucket::take(Some(empty), MINUTE / 3, 3, MINUTE);
+        assert_eq!(retry_after, None);
+        assert!(state.tokens.abs() < 1e-9);
+
+        // A long idle spell refills only to capacity
+        let idle = TokenBucket { tokens: 1.0, refilled_at: 0 };
+        let (state, _) = TokenBucket::take(Some(idle), 10 * MINUTE, 3, MINUTE);
+        assert!((state.tokens - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn zero_limit_refuses_everything() {
+        let (_, retry_after) = TokenBucket::take(None, 0, 0, MINUTE);
+        assert_eq!(retry_after, Some(60));
+    }
+
+    #[test]
+    fn tag_groups_use_normalized_tags() {
+        let mut curry = recipe("Curry");
+        curry.tags = vec!["Quick Dinner".to_string(), "quick_dinner".to_string()];
+        let mut soup = recipe("Soup");
+        soup.tags = vec!["quick-dinner".to_string(), " Vegan ".to_string()];
+        let bread = recipe("Bread");
+
+        let groups = group_recipes(vec![curry, soup, bread], &GroupBy::Tag);
+        let names = |key: &str| -> Vec<String> { groups[key].iter().map(|view| view.recipe.name.clone()).collect() };
+        assert_eq!(groups.keys().collect::<Vec<_>>(), ["_untagged", "quick-dinner", "vegan"]);
+        assert_eq!(names("quick-dinner"), ["Curry", "Soup"]);
+        assert_eq!(names("vegan"), ["Soup"]);
+        assert_eq!(names("_untagged"), ["Bread"]);
+    }
+
+    #[test]
+    fn metrics_render_from_known_keys() {
+        let bucket = MemoryBucket::default();
+        for (method, status) in [("GET", 200), ("GET", 404), ("POST", 201), ("BREW", 418), ("DELETE", 404)] {
+            record_request_metrics(&bucket, method, Some(status)).unwrap();
+        }
+
+        let text = render_metrics(&bucket).unwrap();
+        let samples: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(
+            samples,
+            [
+                "recipe_api_requests_total{method=\"DELETE\"} 1",
+                "recipe_api_requests_total{method=\"GET\"} 2",
+                "recipe_api_requests_total{method=\"OTHER\"} 1",
+                "recipe_api_requests_total{method=\"POST\"} 1",
+                "recipe_api_errors_total{status=\"404\"} 2",
+                "recipe_api_errors_total{status=\"418\"} 1",
+            ]
+        );
+    }
+
+
+    #[test]
+    fn invalid_status_codes_become_500() {
+        assert_eq!(effective_status(42), 500);
+        assert_eq!(effective_status(0), 500);
+        assert_eq!(effective_status(1000), 500);
+        assert_eq!(effective_status(100), 100);
+        assert_eq!(effective_status(204), 204);
+        assert_eq!(effective_status(999), 999);
+    }
+
+
+    #[test]
+    fn reorder_renumbers_steps_in_the_given_sequence() {
+        let mut steps = vec![step(1, "Chop"), step(2, "Simmer"), step(3, "Serve")];
+        reorder_steps(&mut steps, &[3, 1, 2]).unwrap();
+        let listed: Vec<(u8, &str)> = steps.iter().map(|step| (step.order, step.instruction.as_str())).collect();
+        assert_eq!(listed, [(1, "Serve"), (2, "Chop"), (3, "Simmer")]);
+    }
+
+    #[test]
+    fn reorder_refuses_duplicated_legacy_orders() {
+        let mut steps = vec![step(1, "Chop"), step(2, "Simmer"), step(2, "Stir"), step(3, "Serve")];
+        let problems = reorder_steps(&mut steps, &[3, 2, 1]).unwrap_err();
+        assert_eq!(problems, ["more than one step is numbered 2; renumber them before reordering"]);
+        // Nothing was dropped or renumbered
+        let instructions: Vec<&str> = steps.iter().map(|step| step.instruction.as_str()).collect();
+        assert_eq!(instructions, ["Chop", "Simmer", "Stir", "Serve"]);
+        assert_eq!(steps[3].order, 3);
+    }
+
+
+    #[test]
+    fn bulk_delete_goes_through_the_store() {
+        let store = store_with(&["soup", "salad"]);
+        let ids = ["soup", "stew", "soup"].map(String::from);
+
+        let results = delete_recipes_batch(&store, &ids, false).unwrap();
+        let statuses: Vec<u16> = results.iter().map(|result| result.status).collect();
+        assert_eq!(statuses, vec![204, 404, 204]);
+        assert_eq!(store.list().unwrap(), vec!["salad"]);
+        assert_eq!(store.deleted().unwrap().len(), 1);
+        assert!(matches!(store.restore("soup").unwrap(), RestoreOutcome::Restored(_)));
+
+        delete_recipes_batch(&store, &ids[..1], true).unwrap();
+        assert!(store.deleted().unwrap().is_empty());
+    }
+
+    #[test]
+    fn popular_recipes_counts_views_of_live_recipes_only() {
+        let store = store_with(&["soup", "salad", "stew", "pie"]);
+        for id in ["salad", "soup", "soup", "pie", "pie"] {
+            record_view(&store, id);
+        }
+
+        let popular = popular_recipes(&store, 2).unwrap();
+        let ranked: Vec<(&str, u64)> = popular.iter().map(|entry| (entry.recipe.recipe.id.as_str(), entry.views)).collect();
+        assert_eq!(ranked, vec![("pie", 2), ("soup", 2)]);
+
+        store.delete("pie", false).unwrap();
+        let popular = popular_recipes(&store, 10).unwrap();
+        let ranked: Vec<&str> = popular.iter().map(|entry| entry.recipe.recipe.id.as_str()).collect();
+        assert_eq!(ranked, vec!["soup", "salad"]);
+    }
+
+    #[test]
+    fn ratings_and_favorites_need_a_live_recipe() {
+        let store = store_with(&["soup"]);
+
+        assert!(matches!(rate_recipe(&store, "stew", 4), Err(RecipeError::NotFound)));
+        rate_recipe(&store, "soup", 4).unwrap();
+        let summary = rate_recipe(&store, "soup", 5).unwrap();
+        assert_eq!((summary.average_rating, summary.rating_count), (Some(4.5), 2));
+        assert_eq!(served_rating(&store, "soup").map(|rating| rating.rating_count), Some(2));
+
+        assert!(matches!(store.set_favorite("stew", true), Err(RecipeError::NotFound)));
+        store.set_favorite("soup", true).unwrap();
+        assert_eq!(store.favorites().unwrap(), vec!["soup"]);
+        store.delete("soup", false).unwrap();
+        assert!(store.favorites().unwrap().is_empty());
+    }
+}
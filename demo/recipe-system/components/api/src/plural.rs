@@ -0,0 +1,107 @@
+// Rule-based singularization so a search for "tomato" finds "tomatoes" and
+// "berries" finds "berry". This is deliberately not a stemmer: it only undoes
+// the regular English plural endings and leaves anything ambiguous alone.
+
+// Words that end like plurals but aren't
+const INVARIANT: [&str; 5] = ["molasses", "news", "series", "species", "swiss"];
+
+// Singulars whose plural only adds "s", where the rules below would also
+// strip the "e" or turn "ie" into "y"
+const PLAIN_S: [&str; 14] = [
+    "brioche", "brownie", "cache", "calorie", "canoe", "cloche", "cookie", "ganache", "niche", "quiche", "shoe", "sloe",
+    "smoothie", "veggie",
+];
+
+// Singular form of a lowercase word
+pub fn singular(word: &str) -> String {
+    // Three letters or fewer ("gas", "yes") is too short to tell
+    if word.len() <= 3 || INVARIANT.contains(&word) {
+        return word.to_string();
+    }
+    // "glass", "asparagus", "hummus", "pastis"
+    if word.ends_with("ss") || word.ends_with("us") || word.ends_with("is") {
+        return word.to_string();
+    }
+    if let Some(stem) = word.strip_suffix('s').filter(|stem| PLAIN_S.contains(stem)) {
+        return stem.to_string();
+    }
+
+    if let Some(stem) = word.strip_suffix("ies") {
+        // "pies", "ties" and "dies" are too short to have come from a "-y"
+        if word.len() == 4 {
+            return format!("{}ie", stem);
+        }
+        return format!("{}y", stem);
+    }
+    // "tomatoes", "peaches", "radishes", "boxes" drop the whole "es"
+    if ["oes", "ches", "shes", "sses", "xes", "zes"].iter().any(|suffix| word.ends_with(suffix)) {
+        return word[..word.len() - 2].to_string();
+    }
+    word.strip_suffix('s').unwrap_or(word).to_string()
+}
+
+// Lowercases `text` and singularizes each word so plural and singular
+// spellings compare equal. Words are rejoined with single spaces.
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(singular)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Whether `text` mentions `term`, ignoring case and plurals. `term` must
+// already have been through `normalize`.
+pub fn mentions(text: &str, term: &str) -> bool {
+    normalize(text).contains(term)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undoes_regular_plurals() {
+        assert_eq!(singular("tomatoes"), "tomato");
+        assert_eq!(singular("berries"), "berry");
+        assert_eq!(singular("peaches"), "peach");
+        assert_eq!(singular("radishes"), "radish");
+        assert_eq!(singular("onions"), "onion");
+    }
+
+    #[test]
+    fn singular_forms_are_unchanged() {
+        for word in ["tomato", "berry", "peach", "onion", "cookie", "quiche", "pie"] {
+            assert_eq!(singular(word), word);
+        }
+    }
+
+    #[test]
+    fn leaves_words_that_only_look_plural() {
+        for word in ["molasses", "swiss", "glass", "asparagus", "hummus", "gas", "yes"] {
+            assert_eq!(singular(word), word);
+        }
+    }
+
+    #[test]
+    fn keeps_ie_and_che_endings() {
+        assert_eq!(singular("pies"), "pie");
+        assert_eq!(singular("ties"), "tie");
+        assert_eq!(singular("cookies"), "cookie");
+        assert_eq!(singular("brownies"), "brownie");
+        assert_eq!(singular("smoothies"), "smoothie");
+        assert_eq!(singular("quiches"), "quiche");
+        assert_eq!(singular("brioches"), "brioche");
+        assert_eq!(singular("ganaches"), "ganache");
+    }
+
+    #[test]
+    fn normalize_matches_either_spelling() {
+        assert_eq!(normalize("Chocolate Chip Cookies"), "chocolate chip cookie");
+        assert_eq!(normalize("Apple  Pies!"), "apple pie");
+        assert!(mentions("Cherry Tomatoes", &normalize("tomato")));
+        assert!(mentions("Mixed berry", &normalize("Berries")));
+        assert_eq!(normalize("Spinach Quiches"), "spinach quiche");
+    }
+}
@@ -0,0 +1,132 @@
+// Query-string parsing shared by every route that takes filter, sort, or
+// pagination parameters. Keys and values are URL-decoded; a key given more
+// than once keeps every value in order, and a bare `?flag` has an empty value.
+
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct Query {
+    params: HashMap<String, Vec<String>>,
+}
+
+impl Query {
+    // Never fails: empty pairs are skipped and malformed escapes are kept literally
+    pub fn parse(query: &str) -> Self {
+        let mut params: HashMap<String, Vec<String>> = HashMap::new();
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            params.entry(decode(key)).or_default().push(decode(value));
+        }
+
+        Query { params }
+    }
+
+    // First value for `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.get(name).and_then(|values| values.first()).map(String::as_str)
+    }
+
+    // Every value for `name`, in the order given
+    pub fn get_all(&self, name: &str) -> &[String] {
+        self.params.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // True for `?name`, `?name=` and `?name=true`
+    pub fn flag(&self, name: &str) -> bool {
+        matches!(self.get(name), Some("" | "true"))
+    }
+}
+
+// Percent-decoding with `+` as space, as in application/x-www-form-urlencoded
+fn decode(raw: &str) -> String {
+    percent_decode(&raw.replace('+', " "))
+}
+
+// Plain percent-decoding, for path segments where `+` is literal
+pub fn percent_decode(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => match bytes.get(i + 1..i + 3).and_then(hex_byte) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Two hex digits; from_str_radix alone would also accept a sign like "+1"
+fn hex_byte(digits: &[u8]) -> Option<u8> {
+    if !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_keys_keep_every_value_in_order() {
+        let query = Query::parse("tag=vegan&sort=name&tag=quick");
+        assert_eq!(query.get("tag"), Some("vegan"));
+        assert_eq!(query.get_all("tag"), ["vegan", "quick"]);
+        assert_eq!(query.get("sort"), Some("name"));
+        assert_eq!(query.get("limit"), None);
+        assert!(query.get_all("limit").is_empty());
+    }
+
+    #[test]
+    fn keys_and_values_are_decoded() {
+        let query = Query::parse("q=chicken+soup&tag=gluten%2Dfree&na%6De=a%20b");
+        assert_eq!(query.get("q"), Some("chicken soup"));
+        assert_eq!(query.get("tag"), Some("gluten-free"));
+        assert_eq!(query.get("name"), Some("a b"));
+    }
+
+    #[test]
+    fn empty_pairs_are_skipped() {
+        let query = Query::parse("&&sort=name&");
+        assert_eq!(query.get("sort"), Some("name"));
+        assert!(query.get_all("").is_empty());
+        assert!(Query::parse("").get("sort").is_none());
+    }
+
+    #[test]
+    fn flags_accept_bare_empty_and_true() {
+        let query = Query::parse("fuzzy&dry_run=&allow_gaps=true&verbose=false&pretty=1");
+        assert!(query.flag("fuzzy"));
+        assert!(query.flag("dry_run"));
+        assert!(query.flag("allow_gaps"));
+        assert!(!query.flag("verbose"));
+        assert!(!query.flag("pretty"));
+        assert!(!query.flag("missing"));
+    }
+
+    #[test]
+    fn malformed_escapes_are_kept_literally() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%+1"), "%+1");
+        assert_eq!(percent_decode("%E2%82%AC"), "€");
+    }
+
+    #[test]
+    fn path_decoding_keeps_plus_signs() {
+        assert_eq!(percent_decode("salt+pepper"), "salt+pepper");
+        assert_eq!(Query::parse("q=salt+pepper").get("q"), Some("salt pepper"));
+        assert_eq!(Query::parse("q=salt%2Bpepper").get("q"), Some("salt+pepper"));
+    }
+}
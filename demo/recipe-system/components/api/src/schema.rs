@@ -0,0 +1,91 @@
+// JSON Schema (draft 2020-12) for the recipe body accepted by create and
+// update. Hand-written to mirror RecipeJson and validate(): required fields
+// are the ones without an Option, unknown fields are rejected (response-only
+// fields are listed as readOnly, since they're accepted and ignored), and numeric
+// ranges follow the field types. Update it alongside either of those.
+
+use serde_json::{json, Value};
+
+pub fn recipe_schema(difficulty_levels: &[&str], max_time_mins: u64) -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": "/api/recipes/schema",
+        "title": "Recipe",
+        "type": "object",
+        "additionalProperties": false,
+        "required": [
+            "id", "name", "ingredients", "instructions", "servings", "prep_time_mins",
+            "cook_time_mins", "difficulty", "tags", "dietary_info", "created_at", "updated_at"
+        ],
+        "properties": {
+            "id": {
+                "type": "string",
+                "pattern": "^[A-Za-z0-9_-]*$",
+                "maxLength": 128,
+                "description": "Leave empty on create to have one generated. Route names such as `search` aren't allowed."
+            },
+            "name": { "type": "string", "pattern": "\\S" },
+            "description": { "type": ["string", "null"] },
+            "ingredients": { "type": "array", "items": { "$ref": "#/$defs/ingredient" } },
+            "instructions": {
+                "type": "array",
+                "items": { "$ref": "#/$defs/step" },
+                "description": "Step orders must be unique and numbered 1 to n unless ?allow_gaps=true"
+            },
+            "servings": { "type": "integer", "minimum": 1, "maximum": 65535 },
+            "prep_time_mins": { "type": "integer", "minimum": 0, "maximum": max_time_mins },
+            "cook_time_mins": { "type": "integer", "minimum": 0, "maximum": max_time_mins },
+            "difficulty": { "enum": difficulty_levels },
+            "tags": { "type": "array", "items": { "type": "string" } },
+            "dietary_info": { "type": "array", "items": { "type": "string" } },
+            "author": {
+                "type": ["string", "null"],
+                "description": "Taken from the X-Author header on create when omitted"
+            },
+            "created_at": {
+                "$ref": "#/$defs/timestamp",
+                "description": "Unix seconds; set by the server"
+            },
+            "updated_at": {
+                "$ref": "#/$defs/timestamp",
+                "description": "Unix seconds; set by the server"
+            },
+            "total_time_mins": { "readOnly": true, "description": "Computed in responses; ignored on input" },
+            "problems": { "readOnly": true, "description": "Computed in responses; ignored on input" },
+            "_links": { "readOnly": true, "description": "Computed in responses; ignored on input" },
+            "all_notes": { "readOnly": true, "description": "Computed in responses; ignored on input" },
+            "average_rating": { "readOnly": true, "description": "Computed in responses; ignored on input" },
+            "rating_count": { "readOnly": true, "description": "Computed in responses; ignored on input" }
+        },
+        "$defs": {
+            "ingredient": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["name", "amount", "unit", "optional"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "amount": { "type": "number", "minimum": 0 },
+                    "unit": {
+                        "type": "string",
+                        "description": "Known aliases are normalized, e.g. 'tablespoons' to 'tbsp'"
+                    },
+                    "optional": { "type": "boolean" },
+                    "notes": { "type": ["string", "null"] },
+                    "calories_per_unit": { "type": ["number", "null"] }
+                }
+            },
+            "step": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["order", "instruction"],
+                "properties": {
+                    "order": { "type": "integer", "minimum": 1, "maximum": 255 },
+                    "instruction": { "type": "string" },
+                    "duration_mins": { "oneOf": [{ "$ref": "#/$defs/minutes" }, { "type": "null" }] }
+                }
+            },
+            "minutes": { "type": "integer", "minimum": 0, "maximum": 4294967295u64 },
+            "timestamp": { "type": "integer", "minimum": 0 }
+        }
+    })
+}
@@ -0,0 +1,143 @@
+// Ingredient unit rules: the canonical spelling for each known unit, and a
+// conversion table for displaying metric amounts in imperial units.
+// Factors are exact to the precision an f32 amount can carry; US customary
+// volumes are used for fluid ounces and cups.
+
+struct UnitAliases {
+    canonical: &'static str,
+    // Other spellings that mean the same unit, lowercase
+    aliases: &'static [&'static str],
+}
+
+const KNOWN_UNITS: &[UnitAliases] = &[
+    UnitAliases { canonical: "tsp", aliases: &["tsps", "teaspoon", "teaspoons"] },
+    UnitAliases { canonical: "tbsp", aliases: &["tbsps", "tbs", "tablespoon", "tablespoons"] },
+    UnitAliases { canonical: "cup", aliases: &["cups", "c"] },
+    UnitAliases { canonical: "fl-oz", aliases: &["fl oz", "fl. oz", "floz", "fluid ounce", "fluid ounces"] },
+    UnitAliases { canonical: "pint", aliases: &["pints", "pt"] },
+    UnitAliases { canonical: "quart", aliases: &["quarts", "qt"] },
+    UnitAliases { canonical: "gallon", aliases: &["gallons", "gal"] },
+    UnitAliases { canonical: "oz", aliases: &["ounce", "ounces"] },
+    UnitAliases { canonical: "lb", aliases: &["lbs", "pound", "pounds"] },
+    UnitAliases { canonical: "g", aliases: &["gram", "grams", "gr"] },
+    UnitAliases { canonical: "kg", aliases: &["kilogram", "kilograms", "kgs"] },
+    UnitAliases { canonical: "ml", aliases: &["milliliter", "milliliters", "millilitre", "millilitres"] },
+    UnitAliases { canonical: "l", aliases: &["liter", "liters", "litre", "litres"] },
+    UnitAliases { canonical: "pinch", aliases: &["pinches"] },
+    UnitAliases { canonical: "clove", aliases: &["cloves"] },
+    UnitAliases { canonical: "slice", aliases: &["slices"] },
+    UnitAliases { canonical: "can", aliases: &["cans", "tin", "tins"] },
+    UnitAliases { canonical: "piece", aliases: &["pieces", "pc", "pcs"] },
+];
+
+struct Conversion {
+    // Canonical metric unit this entry converts from
+    from: &'static str,
+    to: &'static str,
+    factor: f32,
+}
+
+const METRIC_TO_IMPERIAL: &[Conversion] = &[
+    Conversion { from: "g", to: "oz", factor: 0.035274 },
+    Conversion { from: "kg", to: "lb", factor: 2.204623 },
+    Conversion { from: "ml", to: "fl-oz", factor: 0.033814 },
+    Conversion { from: "l", to: "cup", factor: 4.226753 },
+];
+
+// Canonical spelling of a unit, ignoring case and surrounding whitespace.
+// Returns None for units not in the table; an empty unit (e.g. "2 eggs")
+// canonicalises to itself.
+pub fn canonical(unit: &str) -> Option<&'static str> {
+    let unit = unit.trim().to_lowercase();
+    if unit.is_empty() {
+        return Some("");
+    }
+
+    KNOWN_UNITS
+        .iter()
+        .find(|known| known.canonical == unit || known.aliases.contains(&unit.as_str()))
+        .map(|known| known.canonical)
+}
+
+// Convert a metric amount to its imperial equivalent, rounded to two decimal
+// places. Returns None for units not in the table, which callers should pass
+// through unchanged.
+pub fn to_imperial(amount: f32, unit: &str) -> Option<(f32, &'static str)> {
+    let unit = canonical(unit)?;
+    let conversion = METRIC_TO_IMPERIAL
+        .iter()
+        .find(|conversion| conversion.from == unit)?;
+
+    let converted = (amount * conversion.factor * 100.0).round() / 100.0;
+    Some((converted, conversion.to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factors_match_the_unit_definitions() {
+        // Grams, kilograms and millilitres per ounce, pound, fluid ounce and cup
+        let definitions = [("g", 28.349523), ("kg", 0.45359237), ("ml", 29.57353), ("l", 0.23658824)];
+        for (from, metric_per_imperial) in definitions {
+            let conversion = METRIC_TO_IMPERIAL.iter().find(|conversion| conversion.from == from).unwrap();
+            assert!((conversion.factor * metric_per_imperial - 1.0).abs() < 1e-5, "{}", from);
+        }
+    }
+
+    #[test]
+    fn converts_metric_amounts() {
+        assert_eq!(to_imperial(100.0, "g"), Some((3.53, "oz")));
+        assert_eq!(to_imperial(1.0, "kg"), Some((2.2, "lb")));
+        assert_eq!(to_imperial(250.0, "ml"), Some((8.45, "fl-oz")));
+        assert_eq!(to_imperial(1.0, "l"), Some((4.23, "cup")));
+    }
+
+    #[test]
+    fn converts_aliases_of_metric_units() {
+        assert_eq!(to_imperial(500.0, " Grams "), Some((17.64, "oz")));
+        assert_eq!(to_imperial(2.0, "Litres"), Some((8.45, "cup")));
+    }
+
+    #[test]
+    fn other_units_are_not_converted() {
+        assert_eq!(to_imperial(2.0, "tbsp"), None);
+        assert_eq!(to_imperial(1.0, "cup"), None);
+        assert_eq!(to_imperial(3.0, "handful"), None);
+        assert_eq!(to_imperial(2.0, ""), None);
+    }
+
+    #[test]
+    fn aliases_map_to_one_canonical_unit() {
+        for unit in ["tbsp", "Tbsp", "tablespoon", "Tablespoons", " TBS "] {
+            assert_eq!(canonical(unit), Some("tbsp"), "{}", unit);
+        }
+        assert_eq!(canonical("fl. oz"), Some("fl-oz"));
+        assert_eq!(canonical("Cloves"), Some("clove"));
+    }
+
+    #[test]
+    fn canonical_units_are_their_own_canonical_form() {
+        for known in KNOWN_UNITS {
+            assert_eq!(canonical(known.canonical), Some(known.canonical));
+        }
+    }
+
+    #[test]
+    fn no_alias_is_claimed_by_two_units() {
+        let mut spellings: Vec<&str> = KNOWN_UNITS.iter().flat_map(|known| known.aliases.iter().copied().chain([known.canonical])).collect();
+        let total = spellings.len();
+        spellings.sort();
+        spellings.dedup();
+        assert_eq!(spellings.len(), total);
+    }
+
+    #[test]
+    fn unknown_units_have_no_canonical_form() {
+        assert_eq!(canonical("handful"), None);
+        assert_eq!(canonical("tbspn"), None);
+        // No unit at all, as in "2 eggs", is known
+        assert_eq!(canonical("  "), Some(""));
+    }
+}
@@ -0,0 +1,257 @@
+// Block-style YAML for `Accept: application/yaml` responses. Values are
+// serialized to JSON first and read back into `Node`, which keeps fields in
+// struct order (serde_json's Value would sort them), so this only has to
+// write what JSON can hold. Strings that could read back as anything else
+// (numbers, booleans, null, or text with YAML indicators) are double-quoted
+// using JSON escapes, which YAML's double-quoted style shares.
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Serialize;
+use serde_json::Number;
+
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, serde_json::Error> {
+    let node: Node = serde_json::from_str(&serde_json::to_string(value)?)?;
+    let mut out = String::new();
+    if node.is_collection() {
+        write_block(&mut out, &node, 0, false);
+    } else {
+        out.push_str(&scalar(&node));
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+// A JSON value with its map entries in document order
+enum Node {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+impl Node {
+    // Non-empty maps and sequences, which take block style
+    fn is_collection(&self) -> bool {
+        match self {
+            Node::Seq(items) => !items.is_empty(),
+            Node::Map(entries) => !entries.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Node, E> {
+        Ok(Node::Null)
+    }
+
+    fn visit_bool<E: de::Error>(self, flag: bool) -> Result<Node, E> {
+        Ok(Node::Bool(flag))
+    }
+
+    fn visit_i64<E: de::Error>(self, number: i64) -> Result<Node, E> {
+        Ok(Node::Number(number.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, number: u64) -> Result<Node, E> {
+        Ok(Node::Number(number.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, number: f64) -> Result<Node, E> {
+        Number::from_f64(number).map(Node::Number).ok_or_else(|| E::custom("non-finite number"))
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Node, E> {
+        Ok(Node::String(text.to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Node::Seq(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Node::Map(entries))
+    }
+}
+
+// Writes a non-empty collection one entry per line at `indent`. With
+// `inline`, the first line continues one already started by a `- `.
+fn write_block(out: &mut String, node: &Node, indent: usize, inline: bool) {
+    let pad = " ".repeat(indent);
+    match node {
+        Node::Map(entries) => {
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 || !inline {
+                    out.push_str(&pad);
+                }
+                out.push_str(&string(key));
+                out.push(':');
+                match value {
+                    // Sequences under a key sit at the key's own indent
+                    Node::Seq(_) if value.is_collection() => {
+                        out.push('\n');
+                        write_block(out, value, indent, false);
+                    }
+                    Node::Map(_) if value.is_collection() => {
+                        out.push('\n');
+                        write_block(out, value, indent + 2, false);
+                    }
+                    _ => {
+                        out.push(' ');
+                        out.push_str(&scalar(value));
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+        Node::Seq(items) => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 || !inline {
+                    out.push_str(&pad);
+                }
+                out.push_str("- ");
+                if item.is_collection() {
+                    write_block(out, item, indent + 2, true);
+                } else {
+                    out.push_str(&scalar(item));
+                    out.push('\n');
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// Anything written on one line: true scalars and empty collections
+fn scalar(node: &Node) -> String {
+    match node {
+        Node::Null => "null".to_string(),
+        Node::Bool(flag) => flag.to_string(),
+        Node::Number(number) => number.to_string(),
+        Node::String(text) => string(text),
+        Node::Seq(_) => "[]".to_string(),
+        Node::Map(_) => "{}".to_string(),
+    }
+}
+
+// Plain when it can only read back as this string, double-quoted otherwise
+fn string(text: &str) -> String {
+    if is_plain_safe(text) {
+        text.to_string()
+    } else {
+        serde_json::to_string(text).unwrap_or_default()
+    }
+}
+
+// Deliberately narrow: starts with a letter, holds only letters, digits,
+// spaces and a few punctuation marks YAML gives no meaning to mid-scalar,
+// doesn't end in a space, and isn't a word YAML 1.1 parsers treat as a
+// boolean or null
+fn is_plain_safe(text: &str) -> bool {
+    const RESERVED: &[&str] = &["true", "false", "yes", "no", "on", "off", "y", "n", "null"];
+
+    let Some(first) = text.chars().next() else {
+        return false;
+    };
+    first.is_alphabetic()
+        && !text.ends_with(' ')
+        && text.chars().all(|c| c.is_alphanumeric() || " _-.,/()'".contains(c))
+        && !RESERVED.contains(&text.to_ascii_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Serialize)]
+    struct Recipe {
+        name: &'static str,
+        servings: u16,
+        tags: Vec<&'static str>,
+        ingredients: Vec<Ingredient>,
+        links: serde_json::Value,
+    }
+
+    #[derive(Serialize)]
+    struct Ingredient {
+        name: &'static str,
+        amount: f32,
+        notes: Option<&'static str>,
+    }
+
+    #[test]
+    fn writes_nested_maps_and_sequences_in_field_order() {
+        let value = Recipe {
+            name: "Tomato Soup",
+            servings: 4,
+            tags: vec!["quick", "vegan"],
+            ingredients: vec![
+                Ingredient { name: "tomato", amount: 2.5, notes: None },
+                Ingredient { name: "salt", amount: 1.0, notes: Some("to taste") },
+            ],
+            links: json!({ "self": { "href": "/api/recipes/soup" } }),
+        };
+        let expected = "\
+name: Tomato Soup
+servings: 4
+tags:
+- quick
+- vegan
+ingredients:
+- name: tomato
+  amount: 2.5
+  notes: null
+- name: salt
+  amount: 1.0
+  notes: to taste
+links:
+  self:
+    href: \"/api/recipes/soup\"
+";
+        assert_eq!(to_string(&value).unwrap(), expected);
+    }
+
+    #[test]
+    fn quotes_strings_that_would_read_back_as_something_else() {
+        assert_eq!(string("Soup"), "Soup");
+        assert_eq!(string("Mum's soup, v2"), "Mum's soup, v2");
+        for text in ["", "true", "No", "null", "1.5", "42", "-dash", " padded", "key: value", "# comment", "[list]", "*ref"] {
+            assert!(string(text).starts_with('"'), "{} should be quoted", text);
+        }
+        assert_eq!(string("two\nlines"), "\"two\\nlines\"");
+        assert_eq!(string("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+
+    #[test]
+    fn writes_empty_collections_and_bare_scalars_inline() {
+        assert_eq!(to_string(&json!({ "extra": {}, "tags": [] })).unwrap(), "extra: {}\ntags: []\n");
+        assert_eq!(to_string(&json!([])).unwrap(), "[]\n");
+        assert_eq!(to_string(&json!("text")).unwrap(), "text\n");
+        assert_eq!(to_string(&json!([[1, 2], [3]])).unwrap(), "- - 1\n  - 2\n- - 3\n");
+    }
+}
@@ -248,7 +248,7 @@ echo ""
 
 # List all recipes again
 echo "6. Listing all recipes (should show 2)..."
-curl -s $BASE_URL/api/recipes | jq '. | length'
+curl -s $BASE_URL/api/recipes | jq '.data | length'
 echo ""
 
 # Update a recipe